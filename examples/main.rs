@@ -7,7 +7,6 @@ type PrefixContext<'a> = poise::PrefixContext<'a, Data, Error>;
 
 struct Data {
     votes: Mutex<HashMap<String, u32>>,
-    owner_id: serenity::UserId,
 }
 
 /// Vote for something
@@ -92,14 +91,27 @@ enum MyStringChoice {
 #[poise::command(slash_command)]
 async fn choice(
     ctx: Context<'_>,
-    #[description = "The choice you want to choose"] choice: poise::Wrapper<MyStringChoice>,
+    #[description = "The choice you want to choose"] choice: MyStringChoice,
 ) -> Result<(), Error> {
-    let choice = choice.0;
-
     poise::say_reply(ctx, format!("You entered {:?}", choice)).await?;
     Ok(())
 }
 
+/// Show when a user's account was created
+#[poise::command(context_menu_command = "Account Age")]
+async fn account_age(ctx: Context<'_>, user: serenity::User) -> Result<(), Error> {
+    poise::say_reply(
+        ctx,
+        format!(
+            "{}'s account was created at {}",
+            user.name,
+            user.created_at()
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
 /// Show this help menu
 #[poise::command(track_edits, slash_command)]
 async fn help(
@@ -116,25 +128,24 @@ async fn help(
     Ok(())
 }
 
-async fn is_owner(ctx: crate::PrefixContext<'_>) -> Result<bool, Error> {
-    Ok(ctx.msg.author.id == ctx.data.owner_id)
-}
-
 /// Register slash commands in this guild or globally
 ///
 /// Run with no arguments to register in guild, run with argument "global" to register globally.
-#[poise::command(check = "is_owner", hide_in_help)]
+#[poise::command(owners_only, hide_in_help)]
 async fn register(ctx: PrefixContext<'_>, #[flag] global: bool) -> Result<(), Error> {
-    poise::defaults::register_slash_commands(ctx, global).await?;
+    poise::defaults::register_application_commands(ctx, global).await?;
 
     Ok(())
 }
 
-async fn on_error(error: Error, ctx: poise::ErrorContext<'_, Data, Error>) {
+async fn on_error(error: poise::FrameworkError<Error>, ctx: poise::ErrorContext<'_, Data, Error>) {
     match ctx {
         poise::ErrorContext::Setup => panic!("Failed to start bot: {:?}", error),
         poise::ErrorContext::Command(ctx) => {
-            println!("Error in command `{}`: {:?}", ctx.command().name(), error)
+            // Keep the default responses to failed checks and cooldowns
+            if let Err(error) = poise::defaults::on_rejected_invocation(error, ctx.ctx()).await {
+                println!("Error in command `{}`: {:?}", ctx.command().name(), error)
+            }
         }
         _ => println!("Other error: {:?}", error),
     }
@@ -144,10 +155,12 @@ async fn on_error(error: Error, ctx: poise::ErrorContext<'_, Data, Error>) {
 async fn main() -> Result<(), Error> {
     let mut options = poise::FrameworkOptions {
         prefix_options: poise::PrefixFrameworkOptions {
+            prefix: Some("~".into()),
             edit_tracker: Some(poise::EditTracker::for_timespan(Duration::from_secs(3600))),
             ..Default::default()
         },
         on_error: |error, ctx| Box::pin(on_error(error, ctx)),
+        owners: std::iter::once(serenity::UserId(var("OWNER_ID")?.parse()?)).collect(),
         ..Default::default()
     };
 
@@ -157,15 +170,14 @@ async fn main() -> Result<(), Error> {
     options.command(register(), |f| f);
     options.command(add(), |f| f);
     options.command(choice(), |f| f);
+    options.command(account_age(), |f| f);
 
     let framework = poise::Framework::new(
-        "~".to_owned(), // prefix
         serenity::ApplicationId(var("APPLICATION_ID")?.parse()?),
         move |_ctx, _ready, _framework| {
             Box::pin(async move {
                 Ok(Data {
                     votes: Mutex::new(HashMap::new()),
-                    owner_id: serenity::UserId(var("OWNER_ID")?.parse()?),
                 })
             })
         },
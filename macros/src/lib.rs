@@ -1,5 +1,6 @@
 #![allow(unused)] // temporary
 
+mod modal;
 mod slash_choice_parameter;
 
 use proc_macro::TokenStream;
@@ -22,10 +23,11 @@ impl syn::fold::Fold for AllLifetimesToStatic {
 
 type Error = darling::Error;
 
+/// A list of strings, e.g. `aliases("a", "b")`
 #[derive(Debug, Default)]
-struct Aliases(Vec<String>);
+struct StringList(Vec<String>);
 
-impl darling::FromMeta for Aliases {
+impl darling::FromMeta for StringList {
     fn from_list(items: &[::syn::NestedMeta]) -> darling::Result<Self> {
         items
             .iter()
@@ -35,31 +37,114 @@ impl darling::FromMeta for Aliases {
     }
 }
 
-// #[derive(Debug, darling::FromMeta)]
-// struct BroadcastTypingArgs {
-//     #[darling(default)]
-//     after: f32,
-// }
+/// `#[command(broadcast_typing)]` broadcasts immediately, `#[command(broadcast_typing = 1.5)]`
+/// after the given number of seconds
+#[derive(Debug)]
+enum BroadcastTypingArgs {
+    Immediate,
+    Delay(f32),
+}
+
+impl darling::FromMeta for BroadcastTypingArgs {
+    fn from_word() -> darling::Result<Self> {
+        Ok(Self::Immediate)
+    }
+
+    fn from_value(value: &syn::Lit) -> darling::Result<Self> {
+        let delay = match value {
+            syn::Lit::Float(x) => x.base10_parse::<f32>()?,
+            syn::Lit::Int(x) => x.base10_parse::<f32>()?,
+            syn::Lit::Str(x) => x
+                .value()
+                .parse::<f32>()
+                .map_err(|_| darling::Error::unexpected_lit_type(value))?,
+            _ => return Err(darling::Error::unexpected_lit_type(value)),
+        };
+        Ok(Self::Delay(delay))
+    }
+}
+
+/// Numeric bound of a slash command parameter, e.g. `#[min = 0]`. Negative bounds must be given
+/// as strings (`#[min = "-10"]`) because attributes don't accept negative literals
+#[derive(Debug, Clone, Copy)]
+enum NumericBound {
+    Integer(i64),
+    Float(f64),
+}
+
+impl darling::FromMeta for NumericBound {
+    fn from_value(value: &syn::Lit) -> darling::Result<Self> {
+        let string = match value {
+            syn::Lit::Int(x) => x.base10_digits().to_owned(),
+            syn::Lit::Float(x) => x.base10_digits().to_owned(),
+            syn::Lit::Str(x) => x.value(),
+            _ => return Err(darling::Error::unexpected_lit_type(value)),
+        };
+        if let Ok(x) = string.parse::<i64>() {
+            Ok(Self::Integer(x))
+        } else if let Ok(x) = string.parse::<f64>() {
+            Ok(Self::Float(x))
+        } else {
+            Err(darling::Error::unexpected_lit_type(value))
+        }
+    }
+}
+
+/// Expression given as a string, e.g. `custom_data = "MyData { weight: 5 }"`, because attributes
+/// only accept literals
+#[derive(Debug, Clone)]
+struct ExprString(syn::Expr);
+
+impl darling::FromMeta for ExprString {
+    fn from_string(value: &str) -> darling::Result<Self> {
+        syn::parse_str(value)
+            .map(Self)
+            .map_err(darling::Error::custom)
+    }
+}
+
+impl quote::ToTokens for NumericBound {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        match *self {
+            Self::Integer(x) => x.to_tokens(tokens),
+            Self::Float(x) => x.to_tokens(tokens),
+        }
+    }
+}
 
 /// Representation of the command attribute arguments (`#[command(...)]`)
 #[derive(Default, Debug, darling::FromMeta)]
 #[darling(default)]
 struct CommandAttrArgs {
-    aliases: Aliases,
+    aliases: StringList,
+    examples: StringList,
     track_edits: bool,
-    // broadcast_typing: Option<BroadcastTypingArgs>,
-    broadcast_typing: Option<()>,
+    reuse_response: Option<bool>,
+    broadcast_typing: Option<BroadcastTypingArgs>,
+    reply_to_invocation: Option<syn::Ident>,
     defer_response: Option<bool>,
     explanation_fn: Option<syn::Path>,
     check: Option<syn::Path>,
     on_error: Option<syn::Path>,
+    on_autocomplete_error: Option<syn::Path>,
     rename: Option<String>,
     discard_spare_arguments: bool,
     slash_command: bool,
     hide_in_help: bool,
     ephemeral: bool,
     required_permissions: Option<syn::Ident>,
+    default_member_permissions: Option<syn::Ident>,
     owners_only: bool,
+    guild_only: bool,
+    global_cooldown: Option<u64>,
+    user_cooldown: Option<u64>,
+    guild_cooldown: Option<u64>,
+    channel_cooldown: Option<u64>,
+    subcommand_required: bool,
+    prefix_only: bool,
+    slash_only: bool,
+    context_menu_command: Option<String>,
+    custom_data: Option<ExprString>,
 }
 
 /// Representation of the function parameter attribute arguments
@@ -70,6 +155,9 @@ struct ParamAttrArgs {
     lazy: bool,
     flag: bool,
     rest: bool,
+    min: Option<NumericBound>,
+    max: Option<NumericBound>,
+    autocomplete: Option<syn::Path>,
 }
 
 /// Part of the Invocation struct. Represents a single parameter of a Discord command.
@@ -152,11 +240,14 @@ fn extract_vec_type(t: &syn::Type) -> Option<&syn::Type> {
 }
 
 fn generate_prefix_command_spec(inv: &Invocation) -> Result<proc_macro2::TokenStream, Error> {
-    let description = wrap_option(inv.description);
+    let description = match &inv.description {
+        Some(description) => quote::quote! { Some(#description.into()) },
+        None => quote::quote! { None },
+    };
     let explanation = match &inv.more.explanation_fn {
-        Some(explanation_fn) => quote::quote! { Some(#explanation_fn) },
+        Some(explanation_fn) => quote::quote! { Some(#explanation_fn().into()) },
         None => match &inv.explanation {
-            Some(extracted_explanation) => quote::quote! { Some(|| #extracted_explanation.into()) },
+            Some(extracted_explanation) => quote::quote! { Some(#extracted_explanation.into()) },
             None => quote::quote! { None },
         },
     };
@@ -239,24 +330,31 @@ fn generate_prefix_command_spec(inv: &Invocation) -> Result<proc_macro2::TokenSt
             })
             .collect::<Result<Vec<_>, Error>>()?;
 
-    // Currently you can either fallback to framework setting or opt-in with zero delay. Rest of the
-    // cases are not covered because I don't know how the syntax should look like
-    let broadcast_typing = match inv.more.broadcast_typing {
-        Some(()) => quote::quote! {
-            Some(::poise::BroadcastTypingBehavior::WithDelay(std::time::Duration::from_secs(0)))
-        },
-        None => quote::quote! {
-            None
-        },
-    };
+    let broadcast_typing = generate_broadcast_typing(inv);
 
     let command_name = &inv.command_name;
     let track_edits = inv.more.track_edits;
+    let reuse_response = inv.more.reuse_response.unwrap_or(true);
     let aliases = &inv.more.aliases.0;
+    let examples = &inv.more.examples.0;
     let hide_in_help = &inv.more.hide_in_help;
     let param_names = inv.parameters.iter().map(|p| &p.name).collect::<Vec<_>>();
     let required_permissions = inv.required_permissions;
+    let reply_to_invocation = wrap_option(
+        inv.more
+            .reply_to_invocation
+            .as_ref()
+            .map(|behavior| quote::quote! { ::poise::ReplyBehavior::#behavior }),
+    );
     let owners_only = inv.more.owners_only;
+    let guild_only = inv.more.guild_only;
+    let cooldowns = generate_cooldowns(inv);
+    let subcommand_required = inv.more.subcommand_required;
+    let slash_only = inv.more.slash_only;
+    let custom_data = match &inv.more.custom_data {
+        Some(ExprString(custom_data)) => quote::quote! { Box::new(#custom_data) },
+        None => quote::quote! { Box::new(()) },
+    };
     Ok(quote::quote! {
         ::poise::PrefixCommand {
             name: #command_name,
@@ -266,19 +364,29 @@ fn generate_prefix_command_spec(inv: &Invocation) -> Result<proc_macro2::TokenSt
                     #( #param_specs, )*
                     #wildcard_arg
                 ).await?;
-                inner(#maybe_wrapped_ctx, #( #param_names, )* ).await
+                inner(#maybe_wrapped_ctx, #( #param_names, )* )
+                    .await
+                    .map_err(::poise::FrameworkError::User)
             }),
             options: ::poise::PrefixCommandOptions {
                 track_edits: #track_edits,
+                reuse_response: #reuse_response,
                 broadcast_typing: #broadcast_typing,
+                reply_to_invocation: #reply_to_invocation,
                 aliases: &[ #( #aliases, )* ],
                 inline_help: #description,
                 multiline_help: #explanation,
+                examples: vec![ #( #examples.into(), )* ],
                 check: #check,
                 on_error: #on_error,
                 hide_in_help: #hide_in_help,
                 required_permissions: #required_permissions,
                 owners_only: #owners_only,
+                guild_only: #guild_only,
+                cooldowns: #cooldowns,
+                subcommand_required: #subcommand_required,
+                slash_only: #slash_only,
+                custom_data: #custom_data,
             }
         }
     })
@@ -314,12 +422,35 @@ fn generate_slash_command_spec(inv: &Invocation) -> Result<proc_macro2::TokenStr
         }
 
         let param_name = &param.name;
+        let min_value = param.more.min.map(|min| {
+            quote::quote! { o.0.insert("min_value", ::poise::serde_json::json!(#min)); }
+        });
+        let max_value = param.more.max.map(|max| {
+            quote::quote! { o.0.insert("max_value", ::poise::serde_json::json!(#max)); }
+        });
+        let autocomplete_callback = match &param.more.autocomplete {
+            Some(autocomplete_fn) => quote::quote! {
+                Some(|ctx, partial| Box::pin(#autocomplete_fn(ctx, partial)))
+            },
+            None => quote::quote! { None },
+        };
         parameter_builders.push((
             quote::quote! {
-                |o| (&&&&&std::marker::PhantomData::<#type_>).create(o)
-                    .required(#required)
-                    .name(stringify!(#param_name))
-                    .description(#description)
+                ::poise::SlashCommandParameter {
+                    name: stringify!(#param_name),
+                    builder: |o| {
+                        #min_value
+                        #max_value
+                        (&&&&&std::marker::PhantomData::<#type_>).create(o)
+                            .required(#required)
+                            .name(stringify!(#param_name))
+                            .description(#description)
+                    },
+                    autocomplete_callback: #autocomplete_callback,
+                    name_localizations: std::collections::HashMap::new(),
+                    description_localizations: std::collections::HashMap::new(),
+                    choice_localizations: std::collections::HashMap::new(),
+                }
             },
             required,
         ));
@@ -331,17 +462,20 @@ fn generate_slash_command_spec(inv: &Invocation) -> Result<proc_macro2::TokenStr
         .map(|(builder, _)| builder)
         .collect::<Vec<_>>();
 
-    // Box::pin the check and on_error callbacks in order to store them in a struct
-    let check = match &inv.more.check {
-        Some(check) => quote::quote! { Some(|ctx| Box::pin(#check(::poise::Context::Slash(ctx)))) },
-        None => quote::quote! { None },
-    };
-    let on_error = match &inv.more.on_error {
-        Some(on_error) => quote::quote! {
-            Some(|err, ctx| Box::pin(#on_error(err, ::poise::CommandErrorContext::Slash(ctx))))
-        },
-        None => quote::quote! { None },
-    };
+    // Discord clients enforce the bounds too, but outdated clients or forged requests may not
+    let bounds_checks = inv
+        .parameters
+        .iter()
+        .filter(|p| p.more.min.is_some() || p.more.max.is_some())
+        .map(|p| {
+            let name = &p.name;
+            let min = wrap_option(p.more.min.map(|min| quote::quote! { #min as f64 }));
+            let max = wrap_option(p.more.max.map(|max| quote::quote! { #max as f64 }));
+            quote::quote! {
+                ::poise::check_slash_arg_bounds(args, stringify!(#name), #min, #max)?;
+            }
+        })
+        .collect::<Vec<_>>();
 
     let param_names = inv.parameters.iter().map(|p| &p.name).collect::<Vec<_>>();
     let param_types = inv
@@ -352,38 +486,165 @@ fn generate_slash_command_spec(inv: &Invocation) -> Result<proc_macro2::TokenStr
             false => p.type_.clone(),
         })
         .collect::<Vec<_>>();
-    let defer_response = wrap_option(inv.more.defer_response);
-    let ephemeral = inv.more.ephemeral;
-    let required_permissions = inv.required_permissions;
-    let owners_only = inv.more.owners_only;
+    let options = generate_slash_command_options(inv);
     Ok(quote::quote! {
         ::poise::SlashCommand {
             name: #command_name,
             description: #description,
-            action: |ctx, args| Box::pin(async move {
+            action: ::poise::SlashCommandAction::ChatInput(|ctx, args| Box::pin(async move {
                 // idk why this can't be put in the macro itself (where the lint is triggered) and
                 // why clippy doesn't turn off this lint inside macros in the first place
                 #[allow(clippy::needless_question_mark)]
+                let parsed = async {
+                    #( #bounds_checks )*
+                    ::poise::parse_slash_args!(
+                        ctx.discord, ctx.interaction.guild_id, ctx.interaction.channel_id,
+                        &ctx.interaction.data.resolved, args =>
+                        #( (#param_names: #param_types), )*
+                    ).await
+                }.await;
+                let ( #( #param_names, )* ) = match parsed {
+                    Ok(x) => x,
+                    Err(error) => {
+                        let error = ::poise::SlashArgumentParseError::respond(ctx, args, error).await;
+                        return Err(error.into());
+                    }
+                };
 
-                let ( #( #param_names, )* ) = ::poise::parse_slash_args!(
-                    ctx.discord, ctx.interaction.guild_id, ctx.interaction.channel_id, args =>
-                    #( (#param_names: #param_types), )*
-                ).await?;
-
-                inner(::poise::Context::Slash(ctx), #( #param_names, )*).await
-            }),
+                inner(::poise::Context::Slash(ctx), #( #param_names, )*)
+                    .await
+                    .map_err(::poise::FrameworkError::User)
+            })),
             parameters: {
                 use ::poise::SlashArgumentHack;
                 vec![ #( #parameter_builders, )* ]
             },
-            options: ::poise::SlashCommandOptions {
-                defer_response: #defer_response,
-                check: #check,
-                on_error: #on_error,
-                ephemeral: #ephemeral,
-                required_permissions: #required_permissions,
-                owners_only: #owners_only,
-            }
+            options: #options,
+            name_localizations: std::collections::HashMap::new(),
+            description_localizations: std::collections::HashMap::new(),
+        }
+    })
+}
+
+/// Generates the `SlashCommandOptions` shared by slash commands and context menu commands
+fn generate_slash_command_options(inv: &Invocation) -> proc_macro2::TokenStream {
+    // Box::pin the check and on_error callbacks in order to store them in a struct
+    let check = match &inv.more.check {
+        Some(check) => quote::quote! { Some(|ctx| Box::pin(#check(::poise::Context::Slash(ctx)))) },
+        None => quote::quote! { None },
+    };
+    let on_error = match &inv.more.on_error {
+        Some(on_error) => quote::quote! {
+            Some(|err, ctx| Box::pin(#on_error(err, ::poise::CommandErrorContext::Slash(ctx))))
+        },
+        None => quote::quote! { None },
+    };
+    let on_autocomplete_error = match &inv.more.on_autocomplete_error {
+        Some(on_autocomplete_error) => quote::quote! {
+            Some(|err, ctx| Box::pin(#on_autocomplete_error(err, ctx)))
+        },
+        None => quote::quote! { None },
+    };
+
+    let defer_response = wrap_option(inv.more.defer_response);
+    let broadcast_typing = generate_broadcast_typing(inv);
+    let ephemeral = inv.more.ephemeral;
+    let required_permissions = inv.required_permissions;
+    let default_member_permissions = wrap_option(
+        inv.more
+            .default_member_permissions
+            .as_ref()
+            .map(|perms| quote::quote! { poise::serenity_prelude::Permissions::#perms }),
+    );
+    let owners_only = inv.more.owners_only;
+    let guild_only = inv.more.guild_only;
+    let cooldowns = generate_cooldowns(inv);
+    let prefix_only = inv.more.prefix_only;
+    quote::quote! {
+        ::poise::SlashCommandOptions {
+            defer_response: #defer_response,
+            broadcast_typing: #broadcast_typing,
+            check: #check,
+            on_error: #on_error,
+            on_autocomplete_error: #on_autocomplete_error,
+            ephemeral: #ephemeral,
+            required_permissions: #required_permissions,
+            default_member_permissions: #default_member_permissions,
+            owners_only: #owners_only,
+            guild_only: #guild_only,
+            cooldowns: #cooldowns,
+            prefix_only: #prefix_only,
+        }
+    }
+}
+
+/// Generates the `CooldownConfig` shared by the prefix and slash implementations
+fn generate_cooldowns(inv: &Invocation) -> proc_macro2::TokenStream {
+    let to_duration = |secs: Option<u64>| {
+        wrap_option(secs.map(|secs| quote::quote! { std::time::Duration::from_secs(#secs) }))
+    };
+    let global = to_duration(inv.more.global_cooldown);
+    let user = to_duration(inv.more.user_cooldown);
+    let guild = to_duration(inv.more.guild_cooldown);
+    let channel = to_duration(inv.more.channel_cooldown);
+    quote::quote! {
+        ::poise::CooldownConfig {
+            global: #global,
+            user: #user,
+            guild: #guild,
+            channel: #channel,
+        }
+    }
+}
+
+/// Either fallback to framework setting, or override with immediate or delayed typing
+fn generate_broadcast_typing(inv: &Invocation) -> proc_macro2::TokenStream {
+    match inv.more.broadcast_typing {
+        Some(BroadcastTypingArgs::Immediate) => quote::quote! {
+            Some(::poise::BroadcastTypingBehavior::Immediate)
+        },
+        Some(BroadcastTypingArgs::Delay(secs)) => quote::quote! {
+            Some(::poise::BroadcastTypingBehavior::WithDelay(
+                std::time::Duration::from_secs_f32(#secs)
+            ))
+        },
+        None => quote::quote! {
+            None
+        },
+    }
+}
+
+fn generate_context_menu_command_spec(
+    inv: &Invocation,
+    name: &str,
+) -> Result<proc_macro2::TokenStream, Error> {
+    let param_type = match inv.parameters {
+        [param] => &param.type_,
+        _ => {
+            return Err(syn::Error::new(
+                inv.function.sig.span(),
+                "context menu commands take exactly one parameter, the context menu target",
+            )
+            .into())
+        }
+    };
+
+    let options = generate_slash_command_options(inv);
+    Ok(quote::quote! {
+        ::poise::SlashCommand {
+            name: #name,
+            description: "",
+            action: <#param_type as ::poise::ContextMenuCommandTarget<_, _>>::into_action(
+                |ctx, target| Box::pin(async move {
+                    inner(::poise::Context::Slash(ctx), target)
+                        .await
+                        .map_err(::poise::FrameworkError::User)
+                }),
+            ),
+            parameters: Vec::new(),
+            options: #options,
+            name_localizations: std::collections::HashMap::new(),
+            description_localizations: std::collections::HashMap::new(),
         }
     })
 }
@@ -394,6 +655,28 @@ fn command_inner(args: CommandAttrArgs, mut function: syn::ItemFn) -> Result<Tok
         return Err(syn::Error::new(function.sig.span(), "command function must be async").into());
     }
 
+    if args.prefix_only && args.slash_only {
+        return Err(syn::Error::new(
+            function.sig.span(),
+            "prefix_only and slash_only are mutually exclusive",
+        )
+        .into());
+    }
+    if args.slash_only && !args.slash_command && args.context_menu_command.is_none() {
+        return Err(syn::Error::new(
+            function.sig.span(),
+            "slash_only requires slash_command or context_menu_command to be set",
+        )
+        .into());
+    }
+    if args.slash_command && args.context_menu_command.is_some() {
+        return Err(syn::Error::new(
+            function.sig.span(),
+            "slash_command and context_menu_command are mutually exclusive",
+        )
+        .into());
+    }
+
     // Collect argument names/types/attributes to insert into generated function
     let mut parameters = Vec::new();
     for command_param in function.sig.inputs.iter_mut().skip(1) {
@@ -462,6 +745,11 @@ fn command_inner(args: CommandAttrArgs, mut function: syn::ItemFn) -> Result<Tok
     let command_spec = generate_prefix_command_spec(&invocation)?;
     let slash_command_spec = wrap_option(if args.slash_command {
         Some(generate_slash_command_spec(&invocation)?)
+    } else if let Some(context_menu_name) = &args.context_menu_command {
+        Some(generate_context_menu_command_spec(
+            &invocation,
+            context_menu_name,
+        )?)
     } else {
         None
     });
@@ -469,23 +757,24 @@ fn command_inner(args: CommandAttrArgs, mut function: syn::ItemFn) -> Result<Tok
     // Needed because we're not allowed to have lifetimes in the hacky use case below
     let ctx_type_with_static = syn::fold::fold_type(&mut AllLifetimesToStatic, ctx_type.clone());
 
+    let command_name = &invocation.command_name;
     let function_name = std::mem::replace(&mut function.sig.ident, syn::parse_quote! { inner });
     let function_visibility = &function.vis;
     Ok(TokenStream::from(quote::quote! {
-        #function_visibility fn #function_name() -> (
-            ::poise::PrefixCommand<
-                <#ctx_type_with_static as poise::_GetGenerics>::U,
-                <#ctx_type_with_static as poise::_GetGenerics>::E,
-            >,
-            Option<::poise::SlashCommand<
-                <#ctx_type_with_static as poise::_GetGenerics>::U,
-                <#ctx_type_with_static as poise::_GetGenerics>::E,
-            >>,
-        ) {
+        #function_visibility fn #function_name() -> ::poise::Command<
+            <#ctx_type_with_static as poise::_GetGenerics>::U,
+            <#ctx_type_with_static as poise::_GetGenerics>::E,
+        > {
             #function
 
             use ::poise::serenity_prelude as serenity;
-            (#command_spec, #slash_command_spec)
+            ::poise::Command {
+                name: #command_name,
+                category: None,
+                subcommands: Vec::new(),
+                prefix_action: Some(#command_spec),
+                slash_action: #slash_command_spec,
+            }
         }
     }))
 }
@@ -515,3 +804,16 @@ pub fn slash_choice_parameter(input: TokenStream) -> TokenStream {
         Err(e) => e.write_errors().into(),
     }
 }
+
+#[proc_macro_derive(
+    Modal,
+    attributes(name, placeholder, paragraph, min_length, max_length)
+)]
+pub fn modal(input: TokenStream) -> TokenStream {
+    let struct_ = syn::parse_macro_input!(input as syn::DeriveInput);
+
+    match modal::modal(struct_) {
+        Ok(x) => x,
+        Err(e) => e.write_errors().into(),
+    }
+}
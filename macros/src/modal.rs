@@ -0,0 +1,151 @@
+use proc_macro::TokenStream;
+use syn::spanned::Spanned as _;
+
+/// Representation of the struct attribute arguments
+#[derive(Default, Debug, darling::FromMeta)]
+#[darling(default)]
+struct StructAttribute {
+    name: Option<String>,
+}
+
+/// Representation of the field attribute arguments
+#[derive(Default, Debug, darling::FromMeta)]
+#[darling(default)]
+struct FieldAttribute {
+    name: Option<String>,
+    placeholder: Option<String>,
+    paragraph: bool,
+    min_length: Option<u64>,
+    max_length: Option<u64>,
+}
+
+/// Parses only our own attributes, so that doc comments and the like don't trip up darling
+fn parse_attrs<T: darling::FromMeta>(attrs: Vec<syn::Attribute>) -> Result<T, darling::Error> {
+    let attrs = attrs
+        .into_iter()
+        .filter(|attr| {
+            [
+                "name",
+                "placeholder",
+                "paragraph",
+                "min_length",
+                "max_length",
+            ]
+            .iter()
+            .any(|name| attr.path.is_ident(name))
+        })
+        .map(|attr| attr.parse_meta().map(syn::NestedMeta::Meta))
+        .collect::<Result<Vec<_>, _>>()?;
+    T::from_list(&attrs)
+}
+
+/// Returns whether the type is an `Option<...>`
+fn is_option(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) => matches!(
+            path.path.segments.last(),
+            Some(segment) if segment.ident == "Option"
+        ),
+        _ => false,
+    }
+}
+
+pub fn modal(input: syn::DeriveInput) -> Result<TokenStream, darling::Error> {
+    let fields = match input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) => fields.named,
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "Only structs with named fields can be used as modals",
+            )
+            .into())
+        }
+    };
+
+    let struct_ident = &input.ident;
+    let title = parse_attrs::<StructAttribute>(input.attrs)?
+        .name
+        .unwrap_or_else(|| struct_ident.to_string());
+
+    let mut field_builders = Vec::new();
+    let mut field_parsers = Vec::new();
+    for field in fields {
+        let ty_span = field.ty.span();
+        let required = !is_option(&field.ty);
+        let field_ident = field.ident.expect("named fields have idents");
+        let custom_id = field_ident.to_string();
+        let attrs = parse_attrs::<FieldAttribute>(field.attrs)?;
+
+        let label = attrs.name.unwrap_or_else(|| custom_id.clone());
+        let style = if attrs.paragraph {
+            quote::quote! { poise::serenity_prelude::InputTextStyle::Paragraph }
+        } else {
+            quote::quote! { poise::serenity_prelude::InputTextStyle::Short }
+        };
+        let placeholder = attrs
+            .placeholder
+            .map(|x| quote::quote! { f.placeholder(#x); });
+        let min_length = attrs
+            .min_length
+            .map(|x| quote::quote! { f.min_length(#x); });
+        let max_length_setter = attrs
+            .max_length
+            .map(|x| quote::quote! { f.max_length(#x); });
+        let max_length = match attrs.max_length {
+            Some(x) => {
+                let x = x as usize;
+                quote::quote! { Some(#x) }
+            }
+            None => quote::quote! { None },
+        };
+
+        field_builders.push(quote::quote! {
+            builder.create_action_row(|f| {
+                f.create_input_text(|f| {
+                    f.custom_id(#custom_id)
+                        .label(#label)
+                        .style(#style)
+                        .required(#required);
+                    #placeholder
+                    #min_length
+                    #max_length_setter
+                    f
+                })
+            });
+        });
+
+        let value = quote::quote! { poise::parse_modal_field(data, #custom_id, #max_length)? };
+        field_parsers.push(if required {
+            quote::quote_spanned! { ty_span=>
+                #field_ident: #value.ok_or(poise::ModalParseError::MissingField(#custom_id))?
+            }
+        } else {
+            quote::quote_spanned! { ty_span=> #field_ident: #value }
+        });
+    }
+
+    Ok(quote::quote! {
+        impl poise::Modal for #struct_ident {
+            fn title() -> &'static str {
+                #title
+            }
+
+            fn create_fields(
+                builder: &mut poise::serenity_prelude::CreateComponents,
+            ) -> &mut poise::serenity_prelude::CreateComponents {
+                #( #field_builders )*
+                builder
+            }
+
+            fn parse(
+                data: &poise::serenity_prelude::ModalSubmitInteractionData,
+            ) -> Result<Self, poise::ModalParseError> {
+                Ok(Self { #( #field_parsers, )* })
+            }
+        }
+    }
+    .into())
+}
@@ -1,16 +1,11 @@
 use proc_macro::TokenStream;
 use syn::spanned::Spanned as _;
 
-struct Choice {
-    variant_ident: syn::Ident,
-    display_string: String,
-    id: u64,
-}
-
-/// Representation of the function parameter attribute arguments
-#[derive(Debug, darling::FromMeta)]
+/// Representation of the variant attribute arguments
+#[derive(Default, Debug, darling::FromMeta)]
+#[darling(default)]
 struct VariantAttribute {
-    name: String,
+    name: Option<String>,
 }
 
 pub fn slash_choice_parameter(input: syn::DeriveInput) -> Result<TokenStream, darling::Error> {
@@ -28,7 +23,7 @@ pub fn slash_choice_parameter(input: syn::DeriveInput) -> Result<TokenStream, da
     let mut variant_idents = Vec::new();
     let mut display_strings = Vec::new();
 
-    for (i, variant) in enum_.variants.into_iter().enumerate() {
+    for variant in enum_.variants {
         if !matches!(&variant.fields, syn::Fields::Unit) {
             return Err(syn::Error::new(
                 variant.fields.span(),
@@ -37,21 +32,38 @@ pub fn slash_choice_parameter(input: syn::DeriveInput) -> Result<TokenStream, da
             .into());
         }
 
+        // Only look at our own attributes, so that doc comments and the like don't trip up darling
         let attrs = variant
             .attrs
-            .into_iter()
+            .iter()
+            .filter(|attr| attr.path.is_ident("name"))
             .map(|attr| attr.parse_meta().map(syn::NestedMeta::Meta))
             .collect::<Result<Vec<_>, _>>()?;
-        let display_string = <VariantAttribute as darling::FromMeta>::from_list(&attrs)?.name;
+        let display_string = <VariantAttribute as darling::FromMeta>::from_list(&attrs)?
+            .name
+            .unwrap_or_else(|| variant.ident.to_string());
 
         variant_idents.push(variant.ident);
         display_strings.push(display_string);
     }
 
     let enum_ident = &input.ident;
-    let indices1 = 0_u64..(variant_idents.len() as _);
+    let indices1 = 0_usize..variant_idents.len();
     let indices2 = 0_i32..(variant_idents.len() as _);
     Ok(quote::quote! {
+        impl poise::SlashChoiceParameter for #enum_ident {
+            fn choice_names() -> &'static [&'static str] {
+                &[ #( #display_strings, )* ]
+            }
+
+            fn from_index(index: usize) -> Option<Self> {
+                match index {
+                    #( #indices1 => Some(Self::#variant_idents), )*
+                    _ => None,
+                }
+            }
+        }
+
         #[poise::async_trait]
         impl poise::SlashArgument for #enum_ident {
             async fn extract(
@@ -65,11 +77,10 @@ pub fn slash_choice_parameter(input: syn::DeriveInput) -> Result<TokenStream, da
                     .ok_or(poise::SlashArgError::CommandStructureMismatch(
                         "expected u64",
                     ))?;
-                
-                match choice_key {
-                    #( #indices1 => Ok(Self::#variant_idents), )*
-                    _ => Err(poise::SlashArgError::CommandStructureMismatch("out of bounds choice key")),
-                }
+
+                <Self as poise::SlashChoiceParameter>::from_index(choice_key as usize).ok_or(
+                    poise::SlashArgError::CommandStructureMismatch("out of bounds choice key"),
+                )
             }
 
             fn create(
@@ -83,15 +94,23 @@ pub fn slash_choice_parameter(input: syn::DeriveInput) -> Result<TokenStream, da
 
         impl std::str::FromStr for #enum_ident {
             type Err = poise::InvalidChoice;
-        
+
             fn from_str(s: &str) -> Result<Self, Self::Err> {
-                #(
-                    if s.eq_ignore_ascii_case(#display_strings) {
-                        Ok(Self::#variant_idents)
-                    } else
-                )* {
-                    Err(poise::InvalidChoice)
-                }
+                <Self as poise::SlashChoiceParameter>::from_name(s).ok_or(poise::InvalidChoice)
+            }
+        }
+
+        impl<'a> poise::PopArgument<'a> for #enum_ident {
+            type Err = poise::WrapperArgumentParseError<poise::InvalidChoice>;
+
+            fn pop_from(
+                args: &poise::ArgString<'a>,
+            ) -> Result<(poise::ArgString<'a>, Self), Self::Err> {
+                let (args, string) = <String as poise::PopArgument>::pop_from(args)
+                    .map_err(poise::WrapperArgumentParseError::EmptyArgs)?;
+                let choice = <Self as poise::SlashChoiceParameter>::from_name(&string)
+                    .ok_or(poise::WrapperArgumentParseError::ParseError(poise::InvalidChoice))?;
+                Ok((args, choice))
             }
         }
     }
@@ -0,0 +1,150 @@
+//! Command cooldowns, shared between the prefix and slash implementations of a command
+
+use crate::serenity_prelude as serenity;
+use std::time::{Duration, Instant};
+
+/// How often expired cooldowns are purged from the [`CooldownTracker`]
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Cooldown durations of a command. After an invocation, the command can't be invoked again until
+/// every applicable cooldown has run out.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct CooldownConfig {
+    /// Cooldown across all users, channels and guilds
+    pub global: Option<Duration>,
+    /// Cooldown per user
+    pub user: Option<Duration>,
+    /// Cooldown per guild. Doesn't apply to invocations outside of guilds.
+    pub guild: Option<Duration>,
+    /// Cooldown per channel
+    pub channel: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum CooldownBucket {
+    Global,
+    User(serenity::UserId),
+    Guild(serenity::GuildId),
+    Channel(serenity::ChannelId),
+}
+
+/// The invocation that a cooldown is checked or started for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CooldownContext {
+    pub user_id: serenity::UserId,
+    pub guild_id: Option<serenity::GuildId>,
+    pub channel_id: serenity::ChannelId,
+}
+
+impl CooldownContext {
+    /// Returns the bucket of each cooldown kind, paired with the configured cooldown duration
+    fn buckets(self, config: &CooldownConfig) -> impl Iterator<Item = (CooldownBucket, Duration)> {
+        let buckets = vec![
+            (Some(CooldownBucket::Global), config.global),
+            (Some(CooldownBucket::User(self.user_id)), config.user),
+            (self.guild_id.map(CooldownBucket::Guild), config.guild),
+            (
+                Some(CooldownBucket::Channel(self.channel_id)),
+                config.channel,
+            ),
+        ];
+        buckets
+            .into_iter()
+            .filter_map(|(bucket, duration)| Some((bucket?, duration?)))
+    }
+}
+
+/// Keeps track of running cooldowns. Cooldowns are keyed by the qualified command name, e.g.
+/// `config prefix set`, so that the prefix and slash implementations of a command share their
+/// cooldowns, while subcommands of the same name under different parents don't.
+#[derive(Debug)]
+pub struct CooldownTracker {
+    /// When the cooldown of a command in a bucket runs out
+    expiries: std::collections::HashMap<(String, CooldownBucket), Instant>,
+    last_purge: Instant,
+}
+
+impl Default for CooldownTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CooldownTracker {
+    pub fn new() -> Self {
+        Self {
+            expiries: std::collections::HashMap::new(),
+            last_purge: Instant::now(),
+        }
+    }
+
+    /// Returns how long the cooldown of the given command still runs for this invocation, if at
+    /// all
+    pub fn remaining_cooldown(
+        &self,
+        command_name: &str,
+        config: &CooldownConfig,
+        ctx: CooldownContext,
+    ) -> Option<Duration> {
+        let now = Instant::now();
+        ctx.buckets(config)
+            .filter_map(|(bucket, _)| self.expiries.get(&(command_name.to_owned(), bucket)))
+            .filter_map(|&expiry| expiry.checked_duration_since(now))
+            .filter(|remaining| *remaining > Duration::from_secs(0))
+            .max()
+    }
+
+    /// Starts the cooldowns of the given command for this invocation
+    pub fn start_cooldown(
+        &mut self,
+        command_name: &str,
+        config: &CooldownConfig,
+        ctx: CooldownContext,
+    ) {
+        let now = Instant::now();
+        for (bucket, duration) in ctx.buckets(config) {
+            self.expiries
+                .insert((command_name.to_owned(), bucket), now + duration);
+        }
+
+        if now.duration_since(self.last_purge) >= PURGE_INTERVAL {
+            self.expiries.retain(|_, expiry| *expiry > now);
+            self.last_purge = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_tracker() {
+        let config = CooldownConfig {
+            user: Some(Duration::from_secs(10)),
+            guild: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let ctx = CooldownContext {
+            user_id: serenity::UserId(1),
+            guild_id: None,
+            channel_id: serenity::ChannelId(2),
+        };
+        let other_user_ctx = CooldownContext {
+            user_id: serenity::UserId(3),
+            ..ctx
+        };
+
+        let mut tracker = CooldownTracker::new();
+        assert_eq!(tracker.remaining_cooldown("daily", &config, ctx), None);
+
+        tracker.start_cooldown("daily", &config, ctx);
+        let remaining = tracker.remaining_cooldown("daily", &config, ctx).unwrap();
+        assert!(remaining > Duration::from_secs(9) && remaining <= Duration::from_secs(10));
+        assert_eq!(tracker.remaining_cooldown("weekly", &config, ctx), None);
+        assert_eq!(
+            tracker.remaining_cooldown("daily", &config, other_user_ctx),
+            None
+        );
+    }
+}
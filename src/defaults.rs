@@ -9,24 +9,35 @@ type BoxErrorSendSync = Box<dyn std::error::Error + Send + Sync>;
 /// If the user invoked the command wrong
 /// (i.e. an [`crate::ArgumentParseError`]), the command help is displayed and the user is directed
 /// to the help menu.
-pub async fn on_error<D>(e: BoxErrorSendSync, ctx: crate::ErrorContext<'_, D, BoxErrorSendSync>) {
+pub async fn on_error<D>(
+    e: crate::FrameworkError<BoxErrorSendSync>,
+    ctx: crate::ErrorContext<'_, D, BoxErrorSendSync>,
+) {
     println!("Encountered an error: {:?}", e);
     match ctx {
         crate::ErrorContext::Command(ctx) => {
-            let user_error_msg = if let Some(crate::ArgumentParseError(e)) = e.downcast_ref() {
-                // If we caught an argument parse error, give a helpful error message with the
-                // command explanation if available
-
-                let mut usage = "Please check the help menu for usage information".into();
-                if let crate::CommandErrorContext::Prefix(ctx) = &ctx {
-                    if let Some(multiline_help) = &ctx.command.options.multiline_help {
-                        usage = multiline_help();
-                    }
-                }
-                format!("**{}**\n{}", e, usage)
-            } else {
-                e.to_string()
+            let e = match on_rejected_invocation(e, ctx.ctx()).await {
+                Ok(()) => return,
+                Err(e) => e,
             };
+            let user_error_msg =
+                if let crate::FrameworkError::ArgumentParse(crate::ArgumentParseError(e)) = &e {
+                    // If we caught an argument parse error, give a helpful error message with the
+                    // command explanation if available
+
+                    let mut usage = "Please check the help menu for usage information".into();
+                    if let crate::CommandErrorContext::Prefix(ctx) = &ctx {
+                        if let Some(multiline_help) = &ctx.command.options.multiline_help {
+                            usage = multiline_help.to_string();
+                        }
+                    }
+                    format!("**{}**\n{}", e, usage)
+                } else if let crate::FrameworkError::SlashArgumentParse(_) = e {
+                    // The user has already been sent an ephemeral error message
+                    return;
+                } else {
+                    e.to_string()
+                };
             if let Err(e) = crate::say_reply(ctx.ctx(), user_error_msg).await {
                 println!("Error while user command error: {}", e);
             }
@@ -34,10 +45,55 @@ pub async fn on_error<D>(e: BoxErrorSendSync, ctx: crate::ErrorContext<'_, D, Bo
         crate::ErrorContext::Listener(event) => {
             println!("Error in listener while processing {:?}: {}", event, e)
         }
+        crate::ErrorContext::UnknownCommand(ctx) => {
+            println!(
+                "Error in unknown command callback for \"{}\": {}",
+                ctx.msg.content, e
+            )
+        }
+        crate::ErrorContext::DynamicPrefix(msg) => {
+            println!("Dynamic prefix failed for \"{}\": {}", msg.content, e)
+        }
+        crate::ErrorContext::Autocomplete(ctx) => {
+            println!(
+                "Error in autocomplete of /{} parameter {} for input \"{}\": {}",
+                ctx.ctx.command.name, ctx.parameter_name, ctx.partial_input, e
+            )
+        }
+        crate::ErrorContext::Component(ctx) => {
+            println!(
+                "Error in component handler for \"{}\": {}",
+                ctx.custom_id(),
+                e
+            )
+        }
         crate::ErrorContext::Setup => println!("Setup failed: {}", e),
     }
 }
 
+/// Responds to command invocations that were rejected before the command ran, i.e.
+/// [`crate::FrameworkError::CommandCheckFailed`] and [`crate::FrameworkError::CooldownHit`].
+/// Other errors are handed back.
+///
+/// Cooldown hits are answered with the remaining cooldown, ephemerally for slash commands. Failed
+/// checks are only answered in slash commands, because Discord shows "Interaction failed"
+/// otherwise; prefix invocations are ignored silently.
+pub async fn on_rejected_invocation<U, E: std::fmt::Display>(
+    error: crate::FrameworkError<E>,
+    ctx: crate::Context<'_, U, E>,
+) -> Result<(), crate::FrameworkError<E>> {
+    let response = match (&error, ctx) {
+        (crate::FrameworkError::CooldownHit { .. }, _) => error.to_string(),
+        (crate::FrameworkError::CommandCheckFailed, crate::Context::Slash(ctx)) => {
+            format!("You can't use `/{}` here", ctx.qualified_command_name())
+        }
+        (crate::FrameworkError::CommandCheckFailed, crate::Context::Prefix(_)) => return Ok(()),
+        _ => return Err(error),
+    };
+    let _: Result<_, _> = crate::send_reply(ctx, |f| f.content(response).ephemeral(true)).await;
+    Ok(())
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub enum HelpResponseMode {
     Default,
@@ -54,38 +110,41 @@ pub async fn help<D, E>(
         if let Some(command) = ctx
             .framework()
             .options()
-            .prefix_options
             .commands
             .iter()
-            .map(|cmd_meta| &cmd_meta.command)
+            .filter_map(|cmd_meta| cmd_meta.prefix_action.as_ref())
             .find(|cmd| cmd.name == command)
         {
-            match command.options.multiline_help {
-                Some(f) => f(),
+            let mut help = match &command.options.multiline_help {
+                Some(multiline_help) => multiline_help.to_string(),
                 None => command
                     .options
                     .inline_help
+                    .as_deref()
                     .unwrap_or("No help available")
                     .to_owned(),
+            };
+            if !command.options.examples.is_empty() {
+                help += "\n\nExamples:";
+                for example in &command.options.examples {
+                    help += "\n";
+                    help.push_str(example);
+                }
             }
+            help
         } else {
             format!("No such command `{}`", command)
         }
     } else {
-        let is_also_a_slash_command = |command_name| {
-            let slash_commands = &ctx.framework().options().slash_options.commands;
-            slash_commands.iter().any(|c| c.name == command_name)
-        };
-
-        let mut categories: Vec<(Option<&str>, Vec<&crate::PrefixCommand<_, _>>)> = Vec::new();
-        for cmd_meta in &ctx.framework().options().prefix_options.commands {
+        let mut categories: Vec<(Option<&str>, Vec<&crate::Command<_, _>>)> = Vec::new();
+        for cmd_meta in &ctx.framework().options().commands {
             if let Some((_, commands)) = categories
                 .iter_mut()
                 .find(|(key, _)| *key == cmd_meta.category)
             {
-                commands.push(&cmd_meta.command);
+                commands.push(cmd_meta);
             } else {
-                categories.push((cmd_meta.category, vec![&cmd_meta.command]));
+                categories.push((cmd_meta.category, vec![cmd_meta]));
             }
         }
 
@@ -93,22 +152,41 @@ pub async fn help<D, E>(
         for (category_name, commands) in categories {
             menu += category_name.unwrap_or("Commands");
             menu += ":\n";
-            for command in commands {
+            for cmd_meta in commands {
+                let command = match &cmd_meta.prefix_action {
+                    Some(x) => x,
+                    None => continue,
+                };
                 if command.options.hide_in_help {
                     continue;
                 }
+                // Commands restricted to other guilds aren't registered here
+                let is_also_a_slash_command = cmd_meta
+                    .slash_action
+                    .as_ref()
+                    .map_or(false, |c| !c.options.prefix_only)
+                    && cmd_meta.is_available_in(ctx.guild_id());
+                if command.options.slash_only && !is_also_a_slash_command {
+                    continue;
+                }
 
-                let prefix = if is_also_a_slash_command(command.name) {
+                let prefix = if command.options.slash_only {
                     "/"
                 } else {
-                    ctx.framework().prefix()
+                    ctx.framework().prefix().unwrap_or("")
+                };
+                let also_slash = if !command.options.slash_only && is_also_a_slash_command {
+                    " (also /)"
+                } else {
+                    ""
                 };
 
                 menu += &format!(
-                    "  {}{:<12}{}\n",
+                    "  {}{:<12}{}{}\n",
                     prefix,
                     command.name,
-                    command.options.inline_help.unwrap_or("")
+                    command.options.inline_help.as_deref().unwrap_or(""),
+                    also_slash,
                 );
             }
         }
@@ -145,36 +223,292 @@ pub async fn register_slash_commands<U, E>(
     let guild = match ctx.msg.guild(ctx.discord) {
         Some(x) => x,
         None => {
-            crate::say_prefix_reply(ctx, "Must be called in guild".into()).await?;
+            crate::say_prefix_reply(ctx, "Must be called in guild").await?;
             return Ok(());
         }
     };
 
     if ctx.msg.author.id != guild.owner_id {
-        crate::say_prefix_reply(ctx, "Can only be used by server owner".into()).await?;
+        crate::say_prefix_reply(ctx, "Can only be used by server owner").await?;
         return Ok(());
     }
 
-    let mut commands_builder = serenity::CreateApplicationCommands::default();
-    let commands = &ctx.framework.options().slash_options.commands;
-    for cmd in commands {
-        commands_builder.create_application_command(|f| cmd.create(f));
+    let num_commands = ctx.framework.create_application_commands().0.len();
+
+    crate::say_prefix_reply(ctx, format!("Registering {} commands...", num_commands)).await?;
+    let result = if global {
+        ctx.framework.register_globally(&ctx.discord.http).await
+    } else {
+        ctx.framework
+            .register_in_guild(&ctx.discord.http, guild.id)
+            .await
+    };
+    match result {
+        Ok(_) => {}
+        Err(crate::RegistrationError::Http(e)) => return Err(e),
+        Err(e) => {
+            crate::say_prefix_reply(ctx, e.to_string()).await?;
+            return Ok(());
+        }
     }
+    crate::say_prefix_reply(ctx, "Done!").await?;
 
-    crate::say_prefix_reply(ctx, format!("Registering {} commands...", commands.len())).await?;
-    let json_value = serde_json::Value::Array(commands_builder.0);
-    if global {
-        ctx.discord
-            .http
-            .create_global_application_commands(&json_value)
-            .await?;
+    Ok(())
+}
+
+/// Generic function to register application commands, either in the current guild or globally.
+/// Only allows users from the [owners list](crate::FrameworkOptions::owners) to invoke.
+///
+/// Replies with the number of registered commands, or with the reason why registration failed.
+///
+/// If you want, you can copy paste this help message:
+///
+/// ```ignore
+/// Register application commands in this guild or globally
+///
+/// Run with no arguments to register in guild, run with argument "global" to register globally.
+/// ```
+pub async fn register_application_commands<U, E>(
+    ctx: crate::PrefixContext<'_, U, E>,
+    global: bool,
+) -> Result<(), serenity::Error> {
+    if !ctx.framework.options().owners.contains(&ctx.msg.author.id) {
+        crate::say_prefix_reply(ctx, "Can only be used by bot owners").await?;
+        return Ok(());
+    }
+
+    let result = if global {
+        ctx.framework.register_globally(&ctx.discord.http).await
     } else {
-        ctx.discord
-            .http
-            .create_guild_application_commands(guild.id.0, &json_value)
+        let guild_id = match ctx.msg.guild_id {
+            Some(x) => x,
+            None => {
+                crate::say_prefix_reply(ctx, "Must be called in guild").await?;
+                return Ok(());
+            }
+        };
+        ctx.framework
+            .register_in_guild(&ctx.discord.http, guild_id)
+            .await
+    };
+
+    let reply = match result {
+        Ok(commands) if global => format!("Registered {} commands globally", commands.len()),
+        Ok(commands) => format!("Registered {} commands in this guild", commands.len()),
+        Err(crate::RegistrationError::Http(e)) => return Err(e),
+        Err(e) => e.to_string(),
+    };
+    crate::say_prefix_reply(ctx, reply).await?;
+
+    Ok(())
+}
+
+/// Custom IDs of the buttons added by [`paginate`]
+const PAGINATE_PREVIOUS: &str = "poise_paginate_previous";
+const PAGINATE_NEXT: &str = "poise_paginate_next";
+
+/// Sends the given pages of text with buttons to flip between them.
+///
+/// Only the invoking user can flip pages; other users pressing the buttons get an ephemeral
+/// notice. Once no button was pressed for `timeout`, the buttons are disabled and this function
+/// returns.
+///
+/// ```rust,ignore
+/// poise::defaults::paginate(ctx, &["First page", "Second page"], Duration::from_secs(60)).await?;
+/// ```
+pub async fn paginate<U, E>(
+    ctx: crate::Context<'_, U, E>,
+    pages: &[&str],
+    timeout: std::time::Duration,
+) -> Result<(), serenity::Error> {
+    paginate_with(ctx, pages.len(), timeout, |f, index| {
+        f.content(pages[index].to_owned())
+    })
+    .await
+}
+
+/// Like [`paginate`], but each page is an embed
+pub async fn paginate_embeds<U, E>(
+    ctx: crate::Context<'_, U, E>,
+    pages: &[serenity::CreateEmbed],
+    timeout: std::time::Duration,
+) -> Result<(), serenity::Error> {
+    paginate_with(ctx, pages.len(), timeout, |f, index| {
+        f.set_embeds(vec![pages[index].clone()])
+    })
+    .await
+}
+
+async fn paginate_with<U, E>(
+    ctx: crate::Context<'_, U, E>,
+    page_count: usize,
+    timeout: std::time::Duration,
+    fill_page: impl for<'a, 'b> Fn(
+        &'a mut crate::CreateReply<'b>,
+        usize,
+    ) -> &'a mut crate::CreateReply<'b>,
+) -> Result<(), serenity::Error> {
+    if page_count == 0 {
+        return Ok(());
+    }
+
+    let mut index = 0;
+    let mut handle = crate::send_reply(ctx, |f| {
+        fill_page(f, index).components(|c| paginate_buttons(c, index, page_count, false))
+    })
+    .await?;
+    let message_id = handle.message().await?.id;
+
+    while let Some(press) = serenity::CollectComponentInteraction::new(ctx.discord())
+        .message_id(message_id)
+        .timeout(timeout)
+        .await
+    {
+        if press.user.id != ctx.author().id {
+            respond_not_invoker(ctx, &press).await?;
+            continue;
+        }
+
+        match press.data.custom_id.as_str() {
+            PAGINATE_PREVIOUS => index = index.saturating_sub(1),
+            PAGINATE_NEXT => index = (index + 1).min(page_count - 1),
+            _ => continue,
+        }
+        press
+            .create_interaction_response(ctx.discord(), |r| {
+                r.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
+            })
+            .await?;
+        handle
+            .edit(|f| {
+                fill_page(f, index).components(|c| paginate_buttons(c, index, page_count, false))
+            })
             .await?;
     }
-    crate::say_prefix_reply(ctx, "Done!".to_owned()).await?;
 
-    Ok(())
+    handle
+        .edit(|f| fill_page(f, index).components(|c| paginate_buttons(c, index, page_count, true)))
+        .await
+}
+
+/// Adds the previous and next page buttons. The buttons are disabled on the first and last page
+/// respectively, and when `finished` is set.
+fn paginate_buttons(
+    c: &mut serenity::CreateComponents,
+    index: usize,
+    page_count: usize,
+    finished: bool,
+) -> &mut serenity::CreateComponents {
+    c.create_action_row(|r| {
+        r.create_button(|b| {
+            b.custom_id(PAGINATE_PREVIOUS)
+                .label("◀")
+                .style(serenity::ButtonStyle::Secondary)
+                .disabled(finished || index == 0)
+        })
+        .create_button(|b| {
+            b.custom_id(PAGINATE_NEXT)
+                .label("▶")
+                .style(serenity::ButtonStyle::Secondary)
+                .disabled(finished || index + 1 == page_count)
+        })
+    })
+}
+
+/// Tells a user who pressed a button of someone else's menu that it isn't theirs
+async fn respond_not_invoker<U, E>(
+    ctx: crate::Context<'_, U, E>,
+    press: &serenity::MessageComponentInteraction,
+) -> Result<(), serenity::Error> {
+    press
+        .create_interaction_response(ctx.discord(), |r| {
+            r.kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.content("This menu belongs to someone else")
+                        .flags(serenity::InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await
+}
+
+/// Custom IDs of the buttons added by [`confirm`]
+const CONFIRM_YES: &str = "poise_confirm_yes";
+const CONFIRM_NO: &str = "poise_confirm_no";
+
+/// Asks the invoking user to confirm an action with Yes and No buttons, and returns whether they
+/// chose Yes. If no choice was made within `timeout`, returns `false`.
+///
+/// In slash commands, the prompt is ephemeral. Once a choice was made, the buttons are disabled.
+///
+/// ```rust,ignore
+/// if !poise::defaults::confirm(ctx, "Delete all tags?", Duration::from_secs(30)).await? {
+///     return Ok(());
+/// }
+/// ```
+pub async fn confirm<U, E>(
+    ctx: crate::Context<'_, U, E>,
+    prompt: &str,
+    timeout: std::time::Duration,
+) -> Result<bool, serenity::Error> {
+    let ephemeral = matches!(ctx, crate::Context::Slash(_));
+    let mut handle = crate::send_reply(ctx, |f| {
+        f.content(prompt.to_owned())
+            .ephemeral(ephemeral)
+            .components(|c| confirm_buttons(c, false))
+    })
+    .await?;
+    let message_id = handle.message().await?.id;
+
+    let mut confirmed = false;
+    while let Some(press) = serenity::CollectComponentInteraction::new(ctx.discord())
+        .message_id(message_id)
+        .timeout(timeout)
+        .await
+    {
+        if press.user.id != ctx.author().id {
+            respond_not_invoker(ctx, &press).await?;
+            continue;
+        }
+
+        confirmed = match press.data.custom_id.as_str() {
+            CONFIRM_YES => true,
+            CONFIRM_NO => false,
+            _ => continue,
+        };
+        press
+            .create_interaction_response(ctx.discord(), |r| {
+                r.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
+            })
+            .await?;
+        break;
+    }
+
+    handle
+        .edit(|f| {
+            f.content(prompt.to_owned())
+                .components(|c| confirm_buttons(c, true))
+        })
+        .await?;
+    Ok(confirmed)
+}
+
+/// Adds the Yes and No buttons of [`confirm`]
+fn confirm_buttons(
+    c: &mut serenity::CreateComponents,
+    disabled: bool,
+) -> &mut serenity::CreateComponents {
+    c.create_action_row(|r| {
+        r.create_button(|b| {
+            b.custom_id(CONFIRM_YES)
+                .label("Yes")
+                .style(serenity::ButtonStyle::Danger)
+                .disabled(disabled)
+        })
+        .create_button(|b| {
+            b.custom_id(CONFIRM_NO)
+                .label("No")
+                .style(serenity::ButtonStyle::Secondary)
+                .disabled(disabled)
+        })
+    })
 }
@@ -1,14 +1,56 @@
 //! The central Framework struct that ties everything together.
 
 // Prefix and slash specific implementation details
-mod prefix;
+pub(crate) mod prefix;
 mod slash;
 
 use crate::serenity_prelude as serenity;
 use crate::*;
 
+/// Whether the channel is a public, private or news thread
+fn is_thread(channel: &serenity::GuildChannel) -> bool {
+    matches!(
+        channel.kind,
+        serenity::ChannelType::PublicThread
+            | serenity::ChannelType::PrivateThread
+            | serenity::ChannelType::NewsThread
+    )
+}
+
+/// Looks up a guild channel or thread in the cache, falling back to HTTP
+pub(crate) async fn guild_channel<U, E>(
+    ctx: crate::Context<'_, U, E>,
+    guild: &serenity::Guild,
+    channel_id: serenity::ChannelId,
+) -> Option<serenity::GuildChannel> {
+    let cached = match guild.channels.get(&channel_id) {
+        Some(serenity::Channel::Guild(channel)) => Some(channel.clone()),
+        Some(_other_channel) => {
+            println!(
+                "Warning: guild message was supposedly sent in a non-guild channel. Denying invocation"
+            );
+            return None;
+        }
+        None => guild.threads.iter().find(|t| t.id == channel_id).cloned(),
+    };
+    if let Some(channel) = cached {
+        return Some(channel);
+    }
+
+    // Private threads and threads created before we connected may not be cached
+    match ctx.discord().http.get_channel(channel_id.0).await {
+        Ok(serenity::Channel::Guild(channel)) => Some(channel),
+        _ => None,
+    }
+}
+
+/// Checks whether the given user has the required permissions in the channel of the invocation.
+///
+/// In threads, [`serenity::Permissions::SEND_MESSAGES`] is replaced by
+/// [`serenity::Permissions::SEND_MESSAGES_IN_THREADS`].
 async fn check_permissions<U, E>(
     ctx: crate::Context<'_, U, E>,
+    user_id: serenity::UserId,
     required_permissions: serenity::Permissions,
 ) -> bool {
     if required_permissions.is_empty() {
@@ -20,37 +62,64 @@ async fn check_permissions<U, E>(
         None => return true, // no permission checks in DMs
     };
 
+    // Interactions come with the invoking member's permissions in the channel (including
+    // threads) already computed, so the cache and HTTP aren't needed
+    if let crate::Context::Slash(ctx) = ctx {
+        if ctx.interaction.user.id == user_id {
+            let permissions = ctx
+                .interaction
+                .member
+                .as_ref()
+                .and_then(|member| member.permissions);
+            if let Some(permissions) = permissions {
+                return permissions.contains(required_permissions);
+            }
+        }
+    }
+
     let guild = match ctx.discord().cache.guild(guild_id) {
         Some(x) => x,
         None => return false, // Guild not in cache
     };
 
-    let channel = match guild.channels.get(&ctx.channel_id()) {
-        Some(serenity::Channel::Guild(channel)) => channel,
-        Some(_other_channel) => {
-            println!(
-                "Warning: guild message was supposedly sent in a non-guild channel. Denying invocation"
-            );
-            return false;
-        }
+    let channel = match guild_channel(ctx, &guild, ctx.channel_id()).await {
+        Some(x) => x,
         None => return false,
     };
 
+    // Sending messages in threads is governed by a separate permission
+    let required_permissions = if is_thread(&channel)
+        && required_permissions.contains(serenity::Permissions::SEND_MESSAGES)
+    {
+        (required_permissions - serenity::Permissions::SEND_MESSAGES)
+            | serenity::Permissions::SEND_MESSAGES_IN_THREADS
+    } else {
+        required_permissions
+    };
+
+    // Threads don't have their own permission overwrites; they inherit those of the parent
+    let channel = if is_thread(&channel) {
+        match channel.parent_id {
+            Some(parent_id) => match guild_channel(ctx, &guild, parent_id).await {
+                Some(x) => x,
+                None => return false,
+            },
+            None => return false,
+        }
+    } else {
+        channel
+    };
+
     // If member not in cache (probably because presences intent is not enabled), retrieve via HTTP
-    let member = match guild.members.get(&ctx.author().id) {
+    let member = match guild.members.get(&user_id) {
         Some(x) => x.clone(),
-        None => match ctx
-            .discord()
-            .http
-            .get_member(guild_id.0, ctx.author().id.0)
-            .await
-        {
+        None => match ctx.discord().http.get_member(guild_id.0, user_id.0).await {
             Ok(member) => member,
             Err(_) => return false,
         },
     };
 
-    match guild.user_permissions_in(channel, &member) {
+    match guild.user_permissions_in(&channel, &member) {
         Ok(perms) => perms.contains(required_permissions),
         Err(_) => false,
     }
@@ -65,7 +134,7 @@ async fn check_required_permissions_and_owners_only<U, E>(
         return false;
     }
 
-    if !check_permissions(ctx, required_permissions).await {
+    if !check_permissions(ctx, ctx.author().id, required_permissions).await {
         return false;
     }
 
@@ -73,7 +142,6 @@ async fn check_required_permissions_and_owners_only<U, E>(
 }
 
 pub struct Framework<U, E> {
-    prefix: String,
     user_data: once_cell::sync::OnceCell<U>,
     user_data_setup: std::sync::Mutex<
         Option<
@@ -91,6 +159,24 @@ pub struct Framework<U, E> {
     // The bot ID is embedded in the token so we shouldn't have to do all of this mutex mess
     // But it's kinda messy to get access to the token in the framework
     bot_id: std::sync::Mutex<Option<serenity::UserId>>,
+    // Typing broadcasts of currently running prefix commands, keyed by invocation message
+    typing_broadcasters:
+        std::sync::Mutex<std::collections::HashMap<serenity::MessageId, prefix::DelayedTyping>>,
+    // Running cooldowns of all commands
+    cooldowns: std::sync::Mutex<crate::CooldownTracker>,
+    // Commands waiting for the submission of a modal they sent, keyed by modal custom ID
+    pending_modals: std::sync::Mutex<
+        std::collections::HashMap<
+            String,
+            tokio::sync::oneshot::Sender<serenity::ModalSubmitInteraction>,
+        >,
+    >,
+    // Periodically purges the edit tracker, if there is one. Only holds a weak reference to the
+    // framework, and is aborted on shutdown or drop
+    edit_tracker_purge_task: std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
+    // Shard manager of the serenity client, set once the client is built in `start()`
+    shard_manager:
+        std::sync::Mutex<Option<std::sync::Arc<tokio::sync::Mutex<serenity::ShardManager>>>>,
     // TODO: wrap in RwLock to allow changing framework options while running? Could also replace
     // the edit tracking cache interior mutability
     options: FrameworkOptions<U, E>,
@@ -98,17 +184,22 @@ pub struct Framework<U, E> {
 }
 
 impl<U, E> Framework<U, E> {
-    /// Setup a new blank Framework with a prefix and a callback to provide user data.
+    /// Setup a new blank Framework with a callback to provide user data.
+    ///
+    /// The prefix is configured in [`PrefixFrameworkOptions::prefix`]. Leave it at `None` for
+    /// slash-only bots.
     ///
     /// The user data callback is invoked as soon as the bot is logged. That way, bot data like user
     /// ID or connected guilds can be made available to the user data setup function. The user data
     /// setup is not allowed to return Result because there would be no reasonable
     /// course of action on error.
+    ///
+    /// In debug builds, panics if a slash command violates Discord's constraints (see
+    /// [`crate::validate_application_commands`]).
     pub fn new<F>(
-        prefix: String,
         application_id: serenity::ApplicationId,
         user_data_setup: F,
-        options: FrameworkOptions<U, E>,
+        mut options: FrameworkOptions<U, E>,
     ) -> Self
     where
         F: Send
@@ -120,35 +211,52 @@ impl<U, E> Framework<U, E> {
                 &'a Self,
             ) -> BoxFuture<'a, Result<U, E>>,
     {
+        if let Some(localizer) = options.slash_options.localizer {
+            crate::localize_commands(
+                &mut options.commands,
+                &options.slash_options.locales,
+                localizer,
+            );
+        }
+
+        if cfg!(debug_assertions) {
+            if let Err(e) = crate::validate_application_commands(&options.commands) {
+                panic!("Invalid slash command: {}", e);
+            }
+        }
+
         Self {
-            prefix,
             user_data: once_cell::sync::OnceCell::new(),
             user_data_setup: std::sync::Mutex::new(Some(Box::new(user_data_setup))),
             bot_id: std::sync::Mutex::new(None),
+            typing_broadcasters: std::sync::Mutex::new(std::collections::HashMap::new()),
+            pending_modals: std::sync::Mutex::new(std::collections::HashMap::new()),
+            cooldowns: std::sync::Mutex::new(crate::CooldownTracker::new()),
+            edit_tracker_purge_task: std::sync::Mutex::new(None),
+            shard_manager: std::sync::Mutex::new(None),
             options,
             application_id,
         }
     }
 
+    /// Starts the framework and the serenity client.
+    ///
+    /// Panics if [`FrameworkOptions::validate`] fails.
     pub async fn start(self, builder: serenity::ClientBuilder<'_>) -> Result<(), serenity::Error>
     where
         U: Send + Sync + 'static,
         E: 'static + Send,
     {
+        if let Err(e) = self.options.validate() {
+            panic!("Invalid framework options: {}", e);
+        }
+
         let application_id = self.application_id;
 
         let self_1 = std::sync::Arc::new(self);
         let self_2 = std::sync::Arc::clone(&self_1);
 
-        let edit_track_cache_purge_task = tokio::spawn(async move {
-            loop {
-                if let Some(edit_tracker) = &self_1.options.prefix_options.edit_tracker {
-                    edit_tracker.write().purge();
-                }
-                // not sure if the purging interval should be configurable
-                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
-            }
-        });
+        self_1.start_edit_tracker_purge_task();
 
         let event_handler = EventWrapper(move |ctx, event| {
             let self_2 = std::sync::Arc::clone(&self_2);
@@ -156,16 +264,61 @@ impl<U, E> Framework<U, E> {
                 self_2.event(ctx, event).await;
             }) as _
         });
-        builder
-            .application_id(application_id.0)
-            .event_handler(event_handler)
-            .await?
-            .start()
-            .await?;
+        let result = async {
+            let mut client = builder
+                .application_id(application_id.0)
+                .event_handler(event_handler)
+                .await?;
+            *self_1.shard_manager.lock().unwrap() = Some(client.shard_manager.clone());
+            client.start().await
+        }
+        .await;
 
-        edit_track_cache_purge_task.abort();
+        self_1.shutdown();
 
-        Ok(())
+        result
+    }
+
+    /// Spawns the task which periodically purges the edit tracker. Does nothing if there is no
+    /// edit tracker or the task is already running.
+    fn start_edit_tracker_purge_task(self: &std::sync::Arc<Self>)
+    where
+        U: Send + Sync + 'static,
+        E: 'static + Send,
+    {
+        if self.options.prefix_options.edit_tracker.is_none() {
+            return;
+        }
+        let mut purge_task = self.edit_tracker_purge_task.lock().unwrap();
+        if purge_task.is_some() {
+            return;
+        }
+
+        let framework = std::sync::Arc::downgrade(self);
+        *purge_task = Some(tokio::spawn(async move {
+            // Stops once the framework is dropped
+            while let Some(framework) = framework.upgrade() {
+                let purge_interval = match &framework.options.prefix_options.edit_tracker {
+                    Some(edit_tracker) => {
+                        let mut edit_tracker = edit_tracker.write();
+                        edit_tracker.purge();
+                        edit_tracker.purge_interval()
+                    }
+                    None => break,
+                };
+                drop(framework);
+                tokio::time::sleep(purge_interval).await;
+            }
+        }));
+    }
+
+    /// Stops background tasks of the framework, like the edit tracker purge task.
+    ///
+    /// Called automatically when [`Self::start`] returns or the framework is dropped.
+    pub fn shutdown(&self) {
+        if let Some(purge_task) = self.edit_tracker_purge_task.lock().unwrap().take() {
+            purge_task.abort();
+        }
     }
 
     pub fn options(&self) -> &FrameworkOptions<U, E> {
@@ -176,8 +329,144 @@ impl<U, E> Framework<U, E> {
         self.application_id
     }
 
-    pub fn prefix(&self) -> &str {
-        &self.prefix
+    /// The shard manager of the serenity client. Only available after [`Self::start`] has built
+    /// the client.
+    pub fn shard_manager(
+        &self,
+    ) -> Option<std::sync::Arc<tokio::sync::Mutex<serenity::ShardManager>>> {
+        self.shard_manager.lock().unwrap().clone()
+    }
+
+    /// Builds the application command registration payload for all registered slash commands.
+    ///
+    /// Pass it to [`serenity::GuildId::set_application_commands`] or
+    /// [`serenity::ApplicationCommand::set_global_application_commands`] to register the commands.
+    pub fn create_application_commands(&self) -> serenity::CreateApplicationCommands {
+        crate::create_application_commands(&self.options.commands)
+    }
+
+    /// Registers all slash commands in the given guild, replacing the existing guild commands.
+    ///
+    /// See [`crate::register_in_guild`].
+    pub async fn register_in_guild(
+        &self,
+        http: &serenity::Http,
+        guild_id: serenity::GuildId,
+    ) -> Result<Vec<serenity::ApplicationCommand>, crate::RegistrationError> {
+        crate::register_in_guild(http, &self.options.commands, guild_id).await
+    }
+
+    /// Registers the slash commands for which the filter returns true in the given guild,
+    /// replacing the existing guild commands.
+    ///
+    /// See [`crate::register_in_guild_filtered`].
+    pub async fn register_in_guild_filtered(
+        &self,
+        http: &serenity::Http,
+        guild_id: serenity::GuildId,
+        filter: impl Fn(&crate::Command<U, E>) -> bool,
+    ) -> Result<Vec<serenity::ApplicationCommand>, crate::RegistrationError> {
+        crate::register_in_guild_filtered(http, &self.options.commands, guild_id, filter).await
+    }
+
+    /// Registers the slash commands restricted to certain guilds in each of those guilds.
+    ///
+    /// See [`crate::register_guild_specific`].
+    pub async fn register_guild_specific(
+        &self,
+        http: &serenity::Http,
+    ) -> Result<(), crate::RegistrationError> {
+        crate::register_guild_specific(http, &self.options.commands).await
+    }
+
+    /// Registers all slash commands globally, replacing the existing global commands.
+    ///
+    /// See [`crate::register_globally`].
+    pub async fn register_globally(
+        &self,
+        http: &serenity::Http,
+    ) -> Result<Vec<serenity::ApplicationCommand>, crate::RegistrationError> {
+        crate::register_globally(http, &self.options.commands).await
+    }
+
+    /// Registers all slash commands in the given guild or, if None, globally, but only if they
+    /// differ from the already registered commands.
+    ///
+    /// See [`crate::register_if_changed`].
+    pub async fn register_if_changed(
+        &self,
+        http: &serenity::Http,
+        guild_id: Option<serenity::GuildId>,
+    ) -> Result<crate::RegistrationSummary, crate::RegistrationError> {
+        crate::register_if_changed(http, &self.options.commands, guild_id).await
+    }
+
+    /// Returns the main prefix, if one is configured in [`PrefixFrameworkOptions::prefix`].
+    pub fn prefix(&self) -> Option<&str> {
+        self.options.prefix_options.prefix.as_deref()
+    }
+
+    /// Starts broadcasting typing for the prefix command invoked by the given message, unless a
+    /// broadcast is already running. Stops with [`Self::stop_typing`].
+    pub(crate) fn start_typing(
+        &self,
+        http: &std::sync::Arc<serenity::Http>,
+        msg: &serenity::Message,
+    ) {
+        self.typing_broadcasters
+            .lock()
+            .unwrap()
+            .entry(msg.id)
+            .or_insert_with(|| {
+                prefix::DelayedTyping::start(
+                    http,
+                    msg.channel_id,
+                    std::time::Duration::from_secs(0),
+                )
+            });
+    }
+
+    /// Stops the typing broadcast of the prefix command invoked by the given message, if any.
+    pub(crate) fn stop_typing(&self, invocation_msg_id: serenity::MessageId) {
+        self.typing_broadcasters
+            .lock()
+            .unwrap()
+            .remove(&invocation_msg_id);
+    }
+
+    /// Returns the remaining cooldown if the command is still on cooldown for this invocation.
+    /// Otherwise, starts the cooldown and returns None.
+    fn trigger_cooldown(
+        &self,
+        command_name: &str,
+        config: &crate::CooldownConfig,
+        ctx: crate::CooldownContext,
+    ) -> Option<std::time::Duration> {
+        let mut cooldowns = self.cooldowns.lock().unwrap();
+        if let Some(remaining) = cooldowns.remaining_cooldown(command_name, config, ctx) {
+            return Some(remaining);
+        }
+        cooldowns.start_cooldown(command_name, config, ctx);
+        None
+    }
+
+    /// Registers a command as waiting for the submission of the modal with the given custom ID.
+    /// The returned receiver resolves once the modal is submitted.
+    pub(crate) fn await_modal_submit(
+        &self,
+        custom_id: String,
+    ) -> tokio::sync::oneshot::Receiver<serenity::ModalSubmitInteraction> {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        self.pending_modals
+            .lock()
+            .unwrap()
+            .insert(custom_id, sender);
+        receiver
+    }
+
+    /// Stops waiting for the submission of the modal with the given custom ID, e.g. on timeout
+    pub(crate) fn cancel_modal_submit(&self, custom_id: &str) {
+        self.pending_modals.lock().unwrap().remove(custom_id);
     }
 
     async fn get_user_data(&self) -> &U {
@@ -191,6 +480,49 @@ impl<U, E> Framework<U, E> {
         }
     }
 
+    /// Keeps the edit tracker in sync with deleted messages. If the deleted message is a tracked
+    /// command invocation, its bot responses are deleted as well (if enabled).
+    async fn handle_message_delete(
+        &self,
+        ctx: &serenity::Context,
+        deleted_message_id: serenity::MessageId,
+    ) {
+        let edit_tracker = match &self.options.prefix_options.edit_tracker {
+            Some(x) => x,
+            None => return,
+        };
+
+        let removed_invocation = edit_tracker.write().remove(deleted_message_id);
+        let invocation = match removed_invocation {
+            Some(x) => x,
+            None => {
+                // Maybe it was one of our responses that was deleted
+                edit_tracker.write().remove_bot_response(deleted_message_id);
+                return;
+            }
+        };
+
+        if !self
+            .options
+            .prefix_options
+            .delete_response_on_invocation_delete
+        {
+            return;
+        }
+        for bot_response in invocation.bot_responses() {
+            let delete_result = bot_response
+                .channel_id()
+                .delete_message(ctx, bot_response.id())
+                .await;
+            if let Err(e) = delete_result {
+                println!(
+                    "Warning: couldn't delete bot response when user deleted message: {}",
+                    e
+                );
+            }
+        }
+    }
+
     async fn event(&self, ctx: serenity::Context, event: Event<'_>)
     where
         U: Send + Sync,
@@ -205,7 +537,10 @@ impl<U, E> Framework<U, E> {
                         Ok(user_data) => {
                             let _: Result<_, _> = self.user_data.set(user_data);
                         }
-                        Err(e) => (self.options.on_error)(e, ErrorContext::Setup).await,
+                        Err(e) => {
+                            (self.options.on_error)(FrameworkError::User(e), ErrorContext::Setup)
+                                .await
+                        }
                     }
                 } else {
                     // discarding duplicate Discord bot ready event
@@ -227,37 +562,63 @@ impl<U, E> Framework<U, E> {
                     }
                 }
             }
-            Event::MessageUpdate { event, .. } => {
-                if let Some(edit_tracker) = &self.options.prefix_options.edit_tracker {
-                    let msg = edit_tracker.write().process_message_update(event);
-
-                    if let Err(Some((err, ctx))) =
-                        prefix::dispatch_message(self, &ctx, &msg, true).await
-                    {
+            Event::MessageUpdate {
+                event: update_event,
+                old_if_available,
+                new,
+            } => {
+                if let Some(message_update_listener) = self.options.message_update_listener {
+                    // Partial updates of uncached messages, e.g. resolved link embeds, are passed
+                    // on as is instead of fetching the full message on every such event
+                    let msg = match new {
+                        Some(new) => new.clone(),
+                        None => crate::apply_message_update(old_if_available.clone(), update_event),
+                    };
+                    let data = self.get_user_data().await;
+                    if let Err(e) = message_update_listener(&ctx, &msg, self, data).await {
                         (self.options.on_error)(
-                            err,
-                            crate::ErrorContext::Command(crate::CommandErrorContext::Prefix(ctx)),
+                            FrameworkError::User(e),
+                            ErrorContext::Listener(&event),
                         )
                         .await;
                     }
                 }
+
+                if let Some(edit_tracker) = &self.options.prefix_options.edit_tracker {
+                    let msg = edit_tracker.write().process_message_update(
+                        update_event,
+                        self.options
+                            .prefix_options
+                            .ignore_edits_if_not_yet_responded,
+                        self.options.prefix_options.rerun_on_noop_edit,
+                    );
+
+                    if let Some((msg, _)) = msg.filter(|(_, previously_tracked)| {
+                        *previously_tracked || self.options.prefix_options.execute_untracked_edits
+                    }) {
+                        if let Err(Some((err, ctx))) =
+                            prefix::dispatch_message(self, &ctx, &msg, true).await
+                        {
+                            (self.options.on_error)(
+                                err,
+                                crate::ErrorContext::Command(crate::CommandErrorContext::Prefix(
+                                    ctx,
+                                )),
+                            )
+                            .await;
+                        }
+                    }
+                }
             }
             Event::MessageDelete {
                 deleted_message_id, ..
+            } => self.handle_message_delete(&ctx, *deleted_message_id).await,
+            Event::MessageDeleteBulk {
+                multiple_deleted_messages_ids,
+                ..
             } => {
-                if let Some(edit_tracker) = &self.options.prefix_options.edit_tracker {
-                    let bot_response = edit_tracker
-                        .write()
-                        .find_bot_response(*deleted_message_id)
-                        .cloned();
-                    if let Some(bot_response) = bot_response {
-                        if let Err(e) = bot_response.delete(&ctx).await {
-                            println!(
-                                "Warning: couldn't delete bot response when user deleted message: {}",
-                                e
-                            );
-                        }
-                    }
+                for &deleted_message_id in multiple_deleted_messages_ids {
+                    self.handle_message_delete(&ctx, deleted_message_id).await;
                 }
             }
             Event::InteractionCreate {
@@ -270,6 +631,7 @@ impl<U, E> Framework<U, E> {
                     &interaction.data.name,
                     &interaction.data.options,
                     &std::sync::atomic::AtomicBool::new(false),
+                    &std::sync::atomic::AtomicBool::new(false),
                 )
                 .await
                 {
@@ -284,6 +646,43 @@ impl<U, E> Framework<U, E> {
                     }
                 }
             }
+            Event::InteractionCreate {
+                interaction: serenity::Interaction::Autocomplete(interaction),
+            } => {
+                if let Err((e, error_ctx)) =
+                    slash::dispatch_autocomplete(self, &ctx, interaction).await
+                {
+                    match (e, error_ctx.ctx.command.options.on_autocomplete_error) {
+                        (FrameworkError::User(e), Some(on_error)) => on_error(e, error_ctx).await,
+                        (e, _) => {
+                            (self.options.on_error)(e, ErrorContext::Autocomplete(error_ctx)).await
+                        }
+                    }
+                }
+            }
+            Event::InteractionCreate {
+                interaction: serenity::Interaction::MessageComponent(interaction),
+            } => {
+                if let Err((e, component_ctx)) =
+                    slash::dispatch_component(self, &ctx, interaction).await
+                {
+                    (self.options.on_error)(e, ErrorContext::Component(component_ctx)).await;
+                }
+            }
+            Event::InteractionCreate {
+                interaction: serenity::Interaction::ModalSubmit(interaction),
+            } => {
+                // Submissions of modals that no command is waiting for are left to the listener
+                let pending_modal = self
+                    .pending_modals
+                    .lock()
+                    .unwrap()
+                    .remove(&interaction.data.custom_id);
+                if let Some(sender) = pending_modal {
+                    // If the command stopped waiting in the meantime, there's nobody to notify
+                    let _: Result<_, _> = sender.send(interaction.clone());
+                }
+            }
             _ => {}
         }
 
@@ -292,7 +691,13 @@ impl<U, E> Framework<U, E> {
         if let Err(e) =
             (self.options.listener)(&ctx, &event, self, self.get_user_data().await).await
         {
-            (self.options.on_error)(e, ErrorContext::Listener(&event));
+            (self.options.on_error)(FrameworkError::User(e), ErrorContext::Listener(&event)).await;
         }
     }
 }
+
+impl<U, E> Drop for Framework<U, E> {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
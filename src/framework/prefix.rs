@@ -1,8 +1,9 @@
 use crate::serenity_prelude as serenity;
 
 // Adapted from serenity::Typing
+/// Broadcasts typing as long as this object is alive
 #[derive(Debug)]
-struct DelayedTyping(tokio::sync::oneshot::Sender<()>);
+pub(super) struct DelayedTyping(tokio::sync::oneshot::Sender<()>);
 impl DelayedTyping {
     pub fn start(
         http: &std::sync::Arc<serenity::Http>,
@@ -34,127 +35,300 @@ impl DelayedTyping {
     }
 }
 
-// Returns message with (only) bot prefix removed, if it matches
+/// Splits the message content into the prefix and the remaining content, if the content starts
+/// with the given prefix. The returned prefix borrows from the message content, i.e. it preserves
+/// the user's casing.
+///
+/// Case-insensitive comparison only folds ASCII characters.
+fn split_off_prefix<'a>(
+    content: &'a str,
+    prefix: &str,
+    case_insensitive: bool,
+) -> Option<(&'a str, &'a str)> {
+    let candidate = content.get(..prefix.len())?;
+    let matches = if case_insensitive {
+        candidate.eq_ignore_ascii_case(prefix)
+    } else {
+        candidate == prefix
+    };
+
+    if matches {
+        Some(content.split_at(prefix.len()))
+    } else {
+        None
+    }
+}
+
+/// Splits a mention of the given user off the start of the message content. Both the plain
+/// `<@USER_ID>` and the nickname `<@!USER_ID>` form are accepted.
+///
+/// Returns the raw mention and the remaining content. Whitespace after a mention is always
+/// stripped, because that's how users type it.
+///
+/// If `require_whitespace` is true, the mention must be followed by whitespace or nothing at all.
+fn strip_mention_prefix(
+    content: &str,
+    user_id: serenity::UserId,
+    require_whitespace: bool,
+) -> Option<(&str, &str)> {
+    let rest = content.strip_prefix("<@")?;
+    let rest = rest.strip_prefix('!').unwrap_or(rest);
+    let rest = rest
+        .strip_prefix(user_id.0.to_string().as_str())?
+        .strip_prefix('>')?;
+
+    if require_whitespace && !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let mention_len = content.len() - rest.len();
+    Some((&content[..mention_len], rest.trim_start()))
+}
+
+// Returns the matched prefix and the message with (only) bot prefix removed, if it matches
 async fn strip_prefix<'a, U, E>(
     this: &'a super::Framework<U, E>,
     ctx: &'a serenity::Context,
     msg: &'a serenity::Message,
-) -> Option<&'a str> {
-    if let Some(content) = msg.content.strip_prefix(&this.prefix) {
-        return Some(content);
+) -> Option<(&'a str, &'a str)> {
+    let case_insensitive = this.options.prefix_options.case_insensitive_prefixes;
+
+    if let Some(dynamic_prefix) = this.options.prefix_options.dynamic_prefix {
+        let candidates = match dynamic_prefix(ctx, msg, this.get_user_data().await).await {
+            Ok(x) => x,
+            Err(e) => {
+                (this.options.on_error)(
+                    crate::FrameworkError::User(e),
+                    crate::ErrorContext::DynamicPrefix(msg),
+                )
+                .await;
+                return None;
+            }
+        };
+        if let Some(x) = candidates
+            .iter()
+            .find_map(|prefix| split_off_prefix(&msg.content, prefix, case_insensitive))
+        {
+            return Some(x);
+        }
+    }
+
+    if let Some(prefix_store) = &this.options.prefix_options.prefix_store {
+        let prefix = prefix_store
+            .get(msg.guild_id, this.get_user_data().await)
+            .await;
+        if let Some(x) =
+            prefix.and_then(|prefix| split_off_prefix(&msg.content, &prefix, case_insensitive))
+        {
+            return Some(x);
+        }
+    }
+
+    if let Some(prefix) = &this.options.prefix_options.prefix {
+        if let Some(x) = split_off_prefix(&msg.content, prefix, case_insensitive) {
+            return Some(x);
+        }
     }
 
-    if let Some(content) = this
+    if let Some(x) = this
         .options
         .prefix_options
         .additional_prefixes
         .iter()
         .find_map(|prefix| match prefix {
-            crate::Prefix::Literal(prefix) => msg.content.strip_prefix(prefix),
+            crate::Prefix::Literal(prefix) => {
+                split_off_prefix(&msg.content, prefix, case_insensitive)
+            }
             crate::Prefix::Regex(prefix) => {
                 let regex_match = prefix.find(&msg.content)?;
                 if regex_match.start() == 0 {
-                    Some(&msg.content[regex_match.end()..])
+                    Some(msg.content.split_at(regex_match.end()))
                 } else {
                     None
                 }
             }
         })
     {
-        return Some(content);
+        return Some(x);
     }
 
     if this.options.prefix_options.mention_as_prefix {
-        if let Some(content) = this.bot_id.lock().unwrap().and_then(|bot_id| {
-            // Mentions are either <@USER_ID> or <@!USER_ID>
-            msg.content
-                .strip_prefix("<@")?
-                .trim_start_matches('!')
-                .strip_prefix(&bot_id.0.to_string())?
-                .strip_prefix('>')
-        }) {
-            return Some(content);
+        let bot_id = *this.bot_id.lock().unwrap();
+        let require_whitespace = this.options.prefix_options.require_space_after_mention;
+        if let Some(x) =
+            bot_id.and_then(|bot_id| strip_mention_prefix(&msg.content, bot_id, require_whitespace))
+        {
+            return Some(x);
         }
     }
 
-    if let Some(dynamic_prefix) = this.options.prefix_options.dynamic_prefix {
-        if let Some(content) = dynamic_prefix(ctx, msg, this.get_user_data().await).await {
-            return Some(content);
+    if let Some(dynamic_prefix) = this.options.prefix_options.stripped_dynamic_prefix {
+        let content = match dynamic_prefix(ctx, msg, this.get_user_data().await).await {
+            Ok(x) => x,
+            Err(e) => {
+                (this.options.on_error)(
+                    crate::FrameworkError::User(e),
+                    crate::ErrorContext::DynamicPrefix(msg),
+                )
+                .await;
+                return None;
+            }
+        };
+        if let Some(content) = content {
+            // The callback may return content that doesn't borrow from the end of the message, in
+            // which case the prefix can't be determined
+            let prefix = msg
+                .content
+                .len()
+                .checked_sub(content.len())
+                .and_then(|prefix_len| msg.content.get(..prefix_len))
+                .unwrap_or("");
+            return Some((prefix, content));
         }
     }
 
     None
 }
 
-/// Find a command within nested PrefixCommandMeta's by the user message string. Also returns
-/// the arguments, i.e. the remaining string.
+/// Splits the message content after the prefix into the command name and the raw arguments,
+/// i.e. everything after the command name including the separating whitespace
+fn split_command_name(msg_content: &str) -> (&str, &str) {
+    match msg_content.find(char::is_whitespace) {
+        Some(name_end) => msg_content.split_at(name_end),
+        None => (msg_content, ""),
+    }
+}
+
+/// Whether the given name refers to the command, either via its main name or via one of its
+/// aliases. Used for both top-level commands and subcommands.
+fn command_matches<U, E>(
+    command: &crate::PrefixCommand<U, E>,
+    name: &str,
+    case_insensitive: bool,
+) -> bool {
+    let considered_equal = |a: &str| {
+        if case_insensitive {
+            a.eq_ignore_ascii_case(name)
+        } else {
+            a == name
+        }
+    };
+
+    considered_equal(command.name) || command.options.aliases.iter().any(|a| considered_equal(a))
+}
+
+/// A command in a prefix command chain: the command definition, its prefix implementation, and
+/// the name or alias it was invoked with
+type ChainLink<'a, U, E> = (
+    &'a crate::Command<U, E>,
+    &'a crate::PrefixCommand<U, E>,
+    &'a str,
+);
+
+/// Walks the command tree by the user message string and returns the chain of commands from the
+/// top-level command down to the most deeply nested matching subcommand. Also returns the raw
+/// arguments, i.e. the remaining string.
 ///
-/// May throw an error if a command check fails
-fn find_command<'a, U, E>(
-    this: &'a super::Framework<U, E>,
-    ctx: &'a serenity::Context,
-    msg: &'a serenity::Message,
-    commands: &'a [crate::PrefixCommandMeta<U, E>],
-    remaining_message: &'a str,
-) -> crate::BoxFuture<
-    'a,
-    Result<
-        Option<(&'a crate::PrefixCommandMeta<U, E>, &'a str)>,
-        (E, crate::PrefixCommandErrorContext<'a, U, E>),
-    >,
->
-where
-    U: Send + Sync,
-{
-    Box::pin(_find_command(this, ctx, msg, commands, remaining_message))
+/// Commands without prefix implementation are skipped.
+fn find_command_chain<'a, U, E>(
+    commands: &'a [crate::Command<U, E>],
+    msg_content: &'a str,
+    case_insensitive: bool,
+) -> Option<(Vec<ChainLink<'a, U, E>>, &'a str)> {
+    let find_matching = |commands: &'a [crate::Command<U, E>], command_name: &'a str| {
+        commands.iter().find_map(|command_meta| {
+            let command = command_meta.prefix_action.as_ref()?;
+            if !command.options.slash_only
+                && command_matches(command, command_name, case_insensitive)
+            {
+                Some((command_meta, command, command_name))
+            } else {
+                None
+            }
+        })
+    };
+
+    let (command_name, mut args) = split_command_name(msg_content);
+    let mut link = find_matching(commands, command_name)?;
+    let mut chain = vec![link];
+
+    // Descend into subcommands for as long as the next word matches a subcommand
+    loop {
+        let (subcommand_name, subcommand_args) = split_command_name(args.trim_start());
+        match find_matching(&link.0.subcommands, subcommand_name) {
+            Some(subcommand_link) => {
+                link = subcommand_link;
+                chain.push(subcommand_link);
+                args = subcommand_args;
+            }
+            None => break,
+        }
+    }
+
+    Some((chain, args))
+}
+
+/// Outcome of [`find_command`]
+enum CommandLookup<'a, U, E> {
+    /// The command, the name or alias it was invoked with, the raw arguments, and the qualified
+    /// name of the command definition, e.g. `config prefix set`
+    Found(&'a crate::PrefixCommand<U, E>, &'a str, &'a str, String),
+    /// No command matches the message
+    NotFound,
+    /// A matching command exists, but this invocation isn't allowed to run it, because the command
+    /// is guild-only or the user is missing permissions. Failed checks are returned as
+    /// [`crate::FrameworkError::CommandCheckFailed`] instead
+    Rejected,
 }
 
-async fn _find_command<'a, U, E>(
+/// Find a command within the nested commands by the user message string. Also returns
+/// the name or alias the command was invoked with and the raw arguments, i.e. the remaining
+/// string.
+///
+/// The required permissions and checks of the command and all its parent commands must pass.
+///
+/// May throw an error if a command check fails
+async fn find_command<'a, U, E>(
     this: &'a super::Framework<U, E>,
     ctx: &'a serenity::Context,
     msg: &'a serenity::Message,
-    commands: &'a [crate::PrefixCommandMeta<U, E>],
-    remaining_message: &'a str,
+    prefix: &'a str,
+    msg_content: &'a str,
 ) -> Result<
-    Option<(&'a crate::PrefixCommandMeta<U, E>, &'a str)>,
-    (E, crate::PrefixCommandErrorContext<'a, U, E>),
+    CommandLookup<'a, U, E>,
+    (
+        crate::FrameworkError<E>,
+        crate::PrefixCommandErrorContext<'a, U, E>,
+    ),
 >
 where
     U: Send + Sync,
 {
-    let considered_equal = if this.options.prefix_options.case_insensitive_commands {
-        |a: &str, b: &str| a.eq_ignore_ascii_case(b)
-    } else {
-        |a: &str, b: &str| a == b
-    };
-
-    let (command_name, remaining_message) = {
-        let mut iter = remaining_message.splitn(2, char::is_whitespace);
-        (iter.next().unwrap(), iter.next().unwrap_or("").trim_start())
+    let (chain, args) = match find_command_chain(
+        &this.options.commands,
+        msg_content,
+        this.options.prefix_options.case_insensitive_commands,
+    ) {
+        Some(x) => x,
+        None => return Ok(CommandLookup::NotFound),
     };
 
-    let mut first_matching_command = None;
-    for command_meta in commands {
-        let command = &command_meta.command;
-
-        let primary_name_matches = considered_equal(command.name, command_name);
-        let alias_matches = command
-            .options
-            .aliases
-            .iter()
-            .any(|alias| considered_equal(alias, command_name));
-        if !primary_name_matches && !alias_matches {
-            continue;
-        }
-
+    for &(command_meta, command, invoked_command_name) in &chain {
         let prefix_ctx = crate::PrefixContext {
             discord: ctx,
             msg,
+            prefix,
+            invoked_command_name,
+            raw_args: args,
             framework: this,
             data: this.get_user_data().await,
-            command: Some(&command_meta.command),
+            command: Some(command),
         };
 
+        if command.options.guild_only && msg.guild_id.is_none() {
+            return Ok(CommandLookup::Rejected);
+        }
+
         // Make sure that user has required permissions
         if !super::check_required_permissions_and_owners_only(
             crate::Context::Prefix(prefix_ctx),
@@ -163,99 +337,272 @@ where
         )
         .await
         {
-            continue;
+            return Ok(CommandLookup::Rejected);
         }
 
-        // Only continue if command check returns true
-        let command_check = command
-            .options
-            .check
-            .unwrap_or(this.options.prefix_options.command_check);
-        let check_passes = command_check(prefix_ctx).await.map_err(|e| {
-            (
-                e,
-                crate::PrefixCommandErrorContext {
-                    command,
-                    ctx: prefix_ctx,
-                    while_checking: true,
-                },
-            )
-        })?;
-        if !check_passes {
-            continue;
+        // Only continue if all checks return true. Order: global, category, command
+        let category_check = command_meta
+            .category
+            .and_then(|category| this.options.prefix_options.category_checks.get(category))
+            .copied();
+        let checks = [
+            Some(this.options.prefix_options.command_check),
+            category_check,
+            command.options.check,
+        ];
+        for check in checks.iter().flatten() {
+            let check_passes = check(prefix_ctx).await.map_err(|e| {
+                (
+                    crate::FrameworkError::User(e),
+                    crate::PrefixCommandErrorContext {
+                        command,
+                        ctx: prefix_ctx,
+                        while_checking: true,
+                    },
+                )
+            })?;
+            if !check_passes {
+                return Err((
+                    crate::FrameworkError::CommandCheckFailed,
+                    crate::PrefixCommandErrorContext {
+                        command,
+                        ctx: prefix_ctx,
+                        while_checking: true,
+                    },
+                ));
+            }
         }
-
-        first_matching_command = Some(
-            if let Some((subcommand_meta, remaining_message)) =
-                find_command(this, ctx, msg, &command_meta.subcommands, remaining_message).await?
-            {
-                (subcommand_meta, remaining_message)
-            } else {
-                (command_meta, remaining_message)
-            },
-        );
-        break;
     }
 
-    Ok(first_matching_command)
+    // The chain always contains at least the top-level command
+    let &(_, command, invoked_command_name) = chain.last().unwrap();
+    // Cooldowns are keyed by the names of the command definitions, which the slash implementation
+    // shares
+    let qualified_name = chain
+        .iter()
+        .map(|(command_meta, _, _)| command_meta.name)
+        .collect::<Vec<_>>()
+        .join(" ");
+    Ok(CommandLookup::Found(
+        command,
+        invoked_command_name,
+        args,
+        qualified_name,
+    ))
+}
+
+/// Invokes the [`crate::PrefixFrameworkOptions::unknown_command`] callback, if set, and passes
+/// any error to the framework's error handler.
+///
+/// `parent_command` is Some if a parent command was found, but no matching subcommand. It's
+/// passed along with the name it was invoked with.
+async fn invoke_unknown_command<'a, U, E>(
+    this: &'a super::Framework<U, E>,
+    ctx: &'a serenity::Context,
+    msg: &'a serenity::Message,
+    prefix: &'a str,
+    parent_command: Option<(&'a crate::PrefixCommand<U, E>, &'a str)>,
+    msg_content: &'a str,
+) where
+    U: Send + Sync,
+{
+    if let Some(unknown_command) = this.options.prefix_options.unknown_command {
+        let (command_name, raw_args) = split_command_name(msg_content);
+        let args = raw_args.trim_start();
+        let ctx = crate::PrefixContext {
+            discord: ctx,
+            msg,
+            prefix,
+            invoked_command_name: parent_command.map_or(command_name, |(_, name)| name),
+            raw_args,
+            framework: this,
+            data: this.get_user_data().await,
+            command: parent_command.map(|(command, _)| command),
+        };
+        if let Err(e) = unknown_command(ctx, command_name, args).await {
+            (this.options.on_error)(
+                crate::FrameworkError::User(e),
+                crate::ErrorContext::UnknownCommand(ctx),
+            )
+            .await;
+        }
+    }
 }
 
 /// Returns
 /// - Ok(()) if a command was successfully dispatched and run
 /// - Err(None) if no command was run but no error happened
-/// - Err(Some(error: UserError)) if any user code yielded an error
+/// - Err(Some(error)) if an error happened while processing the command, e.g. in user code
 pub async fn dispatch_message<'a, U, E>(
     this: &'a super::Framework<U, E>,
     ctx: &'a serenity::Context,
     msg: &'a serenity::Message,
     triggered_by_edit: bool,
-) -> Result<(), Option<(E, crate::PrefixCommandErrorContext<'a, U, E>)>>
+) -> Result<
+    (),
+    Option<(
+        crate::FrameworkError<E>,
+        crate::PrefixCommandErrorContext<'a, U, E>,
+    )>,
+>
 where
     U: Send + Sync,
 {
-    // Strip prefix and whitespace between prefix and command
-    let msg_content = strip_prefix(this, ctx, msg).await.ok_or(None)?.trim_start();
+    if let Some(message_filter) = this.options.prefix_options.message_filter {
+        if !message_filter(ctx, msg).await {
+            return Err(None);
+        }
+    }
 
     // If we know our own ID, and the message author ID is our own, and we aren't supposed to
     // execute our own messages, THEN stop execution.
-    if !this.options.prefix_options.execute_self_messages
-        && *this.bot_id.lock().unwrap() == Some(msg.author.id)
-    {
+    let is_self_message = *this.bot_id.lock().unwrap() == Some(msg.author.id);
+    if !this.options.prefix_options.execute_self_messages && is_self_message {
         return Err(None);
     }
 
-    let (command_meta, args) = find_command(
-        this,
-        ctx,
-        msg,
-        &this.options.prefix_options.commands,
-        msg_content,
-    )
-    .await
-    .map_err(Some)?
-    .ok_or(None)?;
-    let command = &command_meta.command;
+    // Webhook messages are marked as bot messages too, but check webhook_id to be sure
+    let is_bot_message = msg.author.bot || msg.webhook_id.is_some();
+    if this.options.prefix_options.ignore_bots && is_bot_message && !is_self_message {
+        return Err(None);
+    }
+
+    // Strip prefix and, if enabled, whitespace between prefix and command
+    let (prefix, msg_content, prefixless) = match strip_prefix(this, ctx, msg).await {
+        Some((prefix, msg_content)) => (prefix, msg_content, false),
+        None if this.options.prefix_options.execute_prefixless_in_dms && msg.guild_id.is_none() => {
+            ("", msg.content.as_str(), true)
+        }
+        None => return Err(None),
+    };
+    let msg_content = if this.options.prefix_options.ignore_whitespace_after_prefix {
+        msg_content.trim_start()
+    } else {
+        msg_content
+    };
+
+    let (command, invoked_command_name, raw_args, qualified_name) =
+        match find_command(this, ctx, msg, prefix, msg_content)
+            .await
+            .map_err(Some)?
+        {
+            CommandLookup::Found(command, invoked_command_name, raw_args, qualified_name) => {
+                (command, invoked_command_name, raw_args, qualified_name)
+            }
+            CommandLookup::NotFound => {
+                // Most prefix-less DMs are just conversation, not mistyped commands
+                if !prefixless {
+                    invoke_unknown_command(this, ctx, msg, prefix, None, msg_content).await;
+                }
+                return Err(None);
+            }
+            // The command exists, so don't tell the user otherwise
+            CommandLookup::Rejected => return Err(None),
+        };
+    // Any whitespace (including newlines) between the command name and the arguments is skipped
+    let args = raw_args.trim_start();
+
+    // No subcommand matched (otherwise it would have been returned instead of this command)
+    if command.options.subcommand_required {
+        invoke_unknown_command(
+            this,
+            ctx,
+            msg,
+            prefix,
+            Some((command, invoked_command_name)),
+            args,
+        )
+        .await;
+        return Err(None);
+    }
 
     if triggered_by_edit && !command.options.track_edits {
         return Err(None);
     }
 
-    // Typing is broadcasted as long as this object is alive
-    let _typing_broadcaster = match command
+    if this.options.prefix_options.require_send_permissions {
+        let bot_id = *this.bot_id.lock().unwrap();
+        if let Some(bot_id) = bot_id {
+            let ctx = crate::PrefixContext {
+                discord: ctx,
+                msg,
+                prefix,
+                invoked_command_name,
+                raw_args,
+                framework: this,
+                data: this.get_user_data().await,
+                command: Some(command),
+            };
+            if !super::check_permissions(
+                crate::Context::Prefix(ctx),
+                bot_id,
+                serenity::Permissions::SEND_MESSAGES,
+            )
+            .await
+            {
+                return Err(None);
+            }
+        }
+    }
+
+    let cooldown_ctx = crate::CooldownContext {
+        user_id: msg.author.id,
+        guild_id: msg.guild_id,
+        channel_id: msg.channel_id,
+    };
+    if let Some(remaining_cooldown) =
+        this.trigger_cooldown(&qualified_name, &command.options.cooldowns, cooldown_ctx)
+    {
+        let ctx = crate::PrefixContext {
+            discord: ctx,
+            msg,
+            prefix,
+            invoked_command_name,
+            raw_args,
+            framework: this,
+            data: this.get_user_data().await,
+            command: Some(command),
+        };
+        return Err(Some((
+            crate::FrameworkError::CooldownHit { remaining_cooldown },
+            crate::PrefixCommandErrorContext {
+                ctx,
+                command,
+                while_checking: false,
+            },
+        )));
+    }
+
+    if command.options.track_edits {
+        if let Some(edit_tracker) = &this.options.prefix_options.edit_tracker {
+            edit_tracker.write().track_command(msg);
+        }
+    }
+
+    // Typing is broadcasted until the command responds or finishes
+    let typing_delay = match command
         .options
         .broadcast_typing
         .as_ref()
         .unwrap_or(&this.options.prefix_options.broadcast_typing)
     {
         crate::BroadcastTypingBehavior::None => None,
-        crate::BroadcastTypingBehavior::WithDelay(delay) => {
-            Some(DelayedTyping::start(&ctx.http, msg.channel_id, *delay))
-        }
+        crate::BroadcastTypingBehavior::Immediate => Some(std::time::Duration::from_secs(0)),
+        crate::BroadcastTypingBehavior::WithDelay(delay) => Some(*delay),
     };
+    if let Some(delay) = typing_delay {
+        this.typing_broadcasters.lock().unwrap().insert(
+            msg.id,
+            DelayedTyping::start(&ctx.http, msg.channel_id, delay),
+        );
+    }
 
     let ctx = crate::PrefixContext {
         discord: ctx,
         msg,
+        prefix,
+        invoked_command_name,
+        raw_args,
         framework: this,
         data: this.get_user_data().await,
         command: Some(command),
@@ -264,7 +611,26 @@ where
     (this.options.pre_command)(crate::Context::Prefix(ctx)).await;
 
     // Execute command
-    (command.action)(ctx, args).await.map_err(|e| {
+    let result = (command.action)(ctx, args).await;
+    this.stop_typing(msg.id);
+
+    // Delete responses of a previous run which this run didn't send again
+    if command.options.track_edits {
+        if let Some(edit_tracker) = &this.options.prefix_options.edit_tracker {
+            let leftover_responses = edit_tracker.write().finish_command(msg.id);
+            for response in leftover_responses {
+                let delete_result = response
+                    .channel_id()
+                    .delete_message(ctx.discord, response.id())
+                    .await;
+                if let Err(e) = delete_result {
+                    println!("Warning: couldn't delete outdated bot response: {}", e);
+                }
+            }
+        }
+    }
+
+    result.map_err(|e| {
         Some((
             e,
             crate::PrefixCommandErrorContext {
@@ -275,3 +641,287 @@ where
         ))
     })
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_off_prefix() {
+        assert_eq!(split_off_prefix("~help", "~", false), Some(("~", "help")));
+        assert_eq!(split_off_prefix("help", "~", false), None);
+        assert_eq!(split_off_prefix("Pls help", "pls ", false), None);
+        assert_eq!(
+            split_off_prefix("Pls Help", "pls ", true),
+            Some(("Pls ", "Help"))
+        );
+        assert_eq!(split_off_prefix("pl", "pls", true), None);
+        // Prefix length ends inside a multibyte character
+        assert_eq!(split_off_prefix("ä", "a", true), None);
+    }
+
+    #[test]
+    fn test_strip_mention_prefix() {
+        let bot_id = serenity::UserId(123);
+
+        assert_eq!(
+            strip_mention_prefix("<@123> help", bot_id, false),
+            Some(("<@123>", "help"))
+        );
+        assert_eq!(
+            strip_mention_prefix("<@!123> help", bot_id, false),
+            Some(("<@!123>", "help"))
+        );
+        assert_eq!(
+            strip_mention_prefix("<@123>   help", bot_id, false),
+            Some(("<@123>", "help"))
+        );
+        assert_eq!(
+            strip_mention_prefix("<@!123>\nhelp", bot_id, false),
+            Some(("<@!123>", "help"))
+        );
+        assert_eq!(
+            strip_mention_prefix("<@123>", bot_id, false),
+            Some(("<@123>", ""))
+        );
+        assert_eq!(strip_mention_prefix("<@!123", bot_id, false), None);
+        assert_eq!(strip_mention_prefix("<@!!123> help", bot_id, false), None);
+        assert_eq!(strip_mention_prefix("<@1234> help", bot_id, false), None);
+        assert_eq!(strip_mention_prefix("help <@123>", bot_id, false), None);
+
+        assert_eq!(
+            strip_mention_prefix("<@123>help me", bot_id, false),
+            Some(("<@123>", "help me"))
+        );
+        assert_eq!(strip_mention_prefix("<@123>help me", bot_id, true), None);
+        assert_eq!(
+            strip_mention_prefix("<@123> help me", bot_id, true),
+            Some(("<@123>", "help me"))
+        );
+        assert_eq!(
+            strip_mention_prefix("<@!123>", bot_id, true),
+            Some(("<@!123>", ""))
+        );
+    }
+
+    /// Prefix-only command that does nothing, for command lookup tests
+    pub(crate) fn dummy_command<E>(
+        name: &'static str,
+        aliases: &'static [&'static str],
+        subcommands: Vec<crate::Command<(), E>>,
+    ) -> crate::Command<(), E> {
+        crate::Command {
+            name,
+            category: None,
+            subcommands,
+            prefix_action: Some(crate::PrefixCommand {
+                name,
+                action: |_, _| Box::pin(async { Ok(()) }),
+                options: crate::PrefixCommandOptions {
+                    aliases,
+                    ..Default::default()
+                },
+            }),
+            slash_action: None,
+        }
+    }
+
+    fn chain_names<'a>(
+        commands: &'a [crate::Command<(), ()>],
+        msg_content: &'a str,
+        case_insensitive: bool,
+    ) -> Option<(Vec<&'static str>, &'a str)> {
+        let (chain, args) = find_command_chain(commands, msg_content, case_insensitive)?;
+        let names = chain.iter().map(|(_, c, _)| c.name).collect();
+        Some((names, args.trim_start()))
+    }
+
+    #[test]
+    fn test_split_command_name() {
+        assert_eq!(split_command_name("ban"), ("ban", ""));
+        assert_eq!(split_command_name("ban @user"), ("ban", " @user"));
+        assert_eq!(
+            split_command_name("ban   @user  spamming"),
+            ("ban", "   @user  spamming")
+        );
+        assert_eq!(split_command_name("ban\n@user"), ("ban", "\n@user"));
+    }
+
+    #[test]
+    fn test_find_command_chain_nested() {
+        let commands = vec![dummy_command(
+            "config",
+            &[],
+            vec![dummy_command(
+                "set",
+                &[],
+                vec![dummy_command("prefix", &[], vec![])],
+            )],
+        )];
+
+        assert_eq!(
+            chain_names(&commands, "config", false),
+            Some((vec!["config"], ""))
+        );
+        assert_eq!(
+            chain_names(&commands, "config bogus arg", false),
+            Some((vec!["config"], "bogus arg"))
+        );
+        assert_eq!(
+            chain_names(&commands, "config set", false),
+            Some((vec!["config", "set"], ""))
+        );
+        assert_eq!(
+            chain_names(&commands, "config set prefix !", false),
+            Some((vec!["config", "set", "prefix"], "!"))
+        );
+        assert_eq!(
+            chain_names(&commands, "config set prefix prefix", false),
+            Some((vec!["config", "set", "prefix"], "prefix"))
+        );
+        assert_eq!(
+            chain_names(&commands, "config\n set \t prefix   !", false),
+            Some((vec!["config", "set", "prefix"], "!"))
+        );
+        assert_eq!(chain_names(&commands, "set prefix", false), None);
+        assert_eq!(chain_names(&commands, "", false), None);
+    }
+
+    #[test]
+    fn test_find_command_chain_aliases_and_case() {
+        let commands = vec![dummy_command(
+            "tag",
+            &["t"],
+            vec![dummy_command(
+                "add",
+                &["a"],
+                vec![dummy_command("global", &["g"], vec![])],
+            )],
+        )];
+
+        assert_eq!(
+            chain_names(&commands, "Tag Add foo", true),
+            Some((vec!["tag", "add"], "foo"))
+        );
+        assert_eq!(chain_names(&commands, "Tag Add foo", false), None);
+        assert_eq!(
+            chain_names(&commands, "tag Add foo", false),
+            Some((vec!["tag"], "Add foo"))
+        );
+        assert_eq!(
+            chain_names(&commands, "t a foo", false),
+            Some((vec!["tag", "add"], "foo"))
+        );
+        assert_eq!(
+            chain_names(&commands, "T A G foo", true),
+            Some((vec!["tag", "add", "global"], "foo"))
+        );
+        assert_eq!(
+            chain_names(&commands, "tag a GLOBAL foo", true),
+            Some((vec!["tag", "add", "global"], "foo"))
+        );
+    }
+
+    /// Dummy discord context; it must not be accessed by the code under test
+    fn dummy_discord_context() -> serenity::Context {
+        serenity::Context {
+            data: std::sync::Arc::new(serenity::RwLock::new(serenity::TypeMap::new())),
+            shard: ::serenity::client::bridge::gateway::ShardMessenger::new(
+                futures::channel::mpsc::unbounded().0,
+            ),
+            shard_id: Default::default(),
+            http: Default::default(),
+            cache: Default::default(),
+        }
+    }
+
+    /// Framework with the given commands whose user data is already set up
+    fn dummy_framework(
+        commands: Vec<crate::Command<(), String>>,
+    ) -> super::super::Framework<(), String> {
+        let framework = super::super::Framework::new(
+            serenity::ApplicationId(1),
+            |_, _, _| Box::pin(async { Ok(()) }),
+            crate::FrameworkOptions {
+                commands,
+                ..Default::default()
+            },
+        );
+        framework.user_data.set(()).unwrap();
+        framework
+    }
+
+    #[tokio::test]
+    async fn test_subcommand_cooldowns_are_separate() {
+        let ctx = dummy_discord_context();
+        let msg = serenity::CustomMessage::new().build();
+
+        let framework = dummy_framework(vec![
+            dummy_command("config", &[], vec![dummy_command("set", &[], vec![])]),
+            dummy_command("tag", &[], vec![dummy_command("set", &[], vec![])]),
+        ]);
+
+        let qualified_name = |content: &'static str| {
+            let framework = &framework;
+            let ctx = &ctx;
+            let msg = &msg;
+            async move {
+                match find_command(framework, ctx, msg, "~", content).await {
+                    Ok(CommandLookup::Found(_, _, _, qualified_name)) => qualified_name,
+                    _ => panic!("command not found: {}", content),
+                }
+            }
+        };
+        let config_set = qualified_name("config set x").await;
+        let tag_set = qualified_name("tag set x").await;
+        assert_eq!(config_set, "config set");
+        assert_eq!(tag_set, "tag set");
+
+        let config = crate::CooldownConfig {
+            user: Some(std::time::Duration::from_secs(10)),
+            ..Default::default()
+        };
+        let cooldown_ctx = crate::CooldownContext {
+            user_id: serenity::UserId(1),
+            guild_id: None,
+            channel_id: serenity::ChannelId(2),
+        };
+        assert_eq!(
+            framework.trigger_cooldown(&config_set, &config, cooldown_ctx),
+            None
+        );
+        assert!(framework
+            .trigger_cooldown(&config_set, &config, cooldown_ctx)
+            .is_some());
+        assert_eq!(
+            framework.trigger_cooldown(&tag_set, &config, cooldown_ctx),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_command_rejected() {
+        let ctx = dummy_discord_context();
+        let msg = serenity::CustomMessage::new().build();
+
+        let mut shutdown = dummy_command("shutdown", &[], vec![]);
+        shutdown.prefix_action.as_mut().unwrap().options.owners_only = true;
+        let mut ban = dummy_command("ban", &[], vec![]);
+        ban.prefix_action.as_mut().unwrap().options.guild_only = true;
+        let framework = dummy_framework(vec![shutdown, ban, dummy_command("ping", &[], vec![])]);
+
+        let lookup = |content: &'static str| find_command(&framework, &ctx, &msg, "~", content);
+        assert!(matches!(
+            lookup("ping").await,
+            Ok(CommandLookup::Found(command, "ping", "", _))
+                if command.name == "ping"
+        ));
+        assert!(matches!(lookup("bogus").await, Ok(CommandLookup::NotFound)));
+        // Commands the user isn't allowed to run aren't reported as unknown
+        assert!(matches!(
+            lookup("shutdown").await,
+            Ok(CommandLookup::Rejected)
+        ));
+        assert!(matches!(lookup("ban").await, Ok(CommandLookup::Rejected)));
+    }
+}
@@ -1,5 +1,55 @@
 use crate::serenity_prelude as serenity;
 
+/// Finds the top-level command with a slash implementation of the given name and kind
+fn find_top_level_command<'a, U, E>(
+    commands: &'a [crate::Command<U, E>],
+    name: &str,
+    kind: serenity::ApplicationCommandType,
+) -> Option<&'a crate::Command<U, E>> {
+    commands.iter().find(|cmd| {
+        cmd.slash_action.as_ref().map_or(false, |slash_command| {
+            slash_command.name == name
+                && !slash_command.options.prefix_only
+                && slash_command.action.matches_kind(kind)
+        })
+    })
+}
+
+/// A command in a slash command chain: the command definition and its slash implementation
+type ChainLink<'a, U, E> = (&'a crate::Command<U, E>, &'a crate::SlashCommand<U, E>);
+
+/// Descends from the given top-level command into the subcommand and subcommand group options of
+/// the interaction. Returns the command chain, from the top-level command down to the invoked
+/// leaf command, and the actual arguments of the leaf command.
+///
+/// Returns None if an invoked subcommand doesn't exist.
+fn find_leaf_command<'a, U, E>(
+    mut command: &'a crate::Command<U, E>,
+    mut options: &'a [serenity::ApplicationCommandInteractionDataOption],
+) -> Option<(
+    Vec<ChainLink<'a, U, E>>,
+    &'a [serenity::ApplicationCommandInteractionDataOption],
+)> {
+    let mut chain = vec![(command, command.slash_action.as_ref()?)];
+
+    // A subcommand invocation arrives as a single option that wraps the actual arguments
+    while let [option] = options {
+        match option.kind {
+            serenity::ApplicationCommandOptionType::SubCommand
+            | serenity::ApplicationCommandOptionType::SubCommandGroup => {}
+            _ => break,
+        }
+        command = command.subcommands.iter().find(|cmd| {
+            cmd.slash_action.as_ref().map_or(false, |slash_command| {
+                slash_command.name == option.name && !slash_command.options.prefix_only
+            })
+        })?;
+        chain.push((command, command.slash_action.as_ref()?));
+        options = &option.options;
+    }
+    Some((chain, options))
+}
+
 pub async fn dispatch_interaction<'a, U, E>(
     this: &'a super::Framework<U, E>,
     ctx: &'a serenity::Context,
@@ -8,20 +58,31 @@ pub async fn dispatch_interaction<'a, U, E>(
     options: &'a [serenity::ApplicationCommandInteractionDataOption],
     // Need to pass this in from outside because of lifetime issues
     has_sent_initial_response: &'a std::sync::atomic::AtomicBool,
-) -> Result<(), (E, crate::SlashCommandErrorContext<'a, U, E>)> {
-    let command = match this
-        .options
-        .slash_options
-        .commands
-        .iter()
-        .find(|cmd| cmd.name == name)
-    {
+    is_deferred: &'a std::sync::atomic::AtomicBool,
+) -> Result<
+    (),
+    (
+        crate::FrameworkError<E>,
+        crate::SlashCommandErrorContext<'a, U, E>,
+    ),
+> {
+    let command = find_top_level_command(&this.options.commands, name, interaction.data.kind);
+    let (chain, options) = match command.and_then(|cmd| find_leaf_command(cmd, options)) {
         Some(x) => x,
         None => {
-            println!("Warning: received unknown interaction \"{}\"", name);
+            (this.options.slash_options.unknown_command_handler)(ctx, interaction).await;
             return Ok(());
         }
     };
+    // The chain always contains at least the top-level command
+    let command = chain.last().unwrap().1;
+    // Cooldowns are keyed by the names of the command definitions, which the prefix
+    // implementation shares
+    let qualified_name = chain
+        .iter()
+        .map(|(command_meta, _)| command_meta.name)
+        .collect::<Vec<_>>()
+        .join(" ");
 
     let ctx = crate::SlashContext {
         data: this.get_user_data().await,
@@ -30,52 +91,164 @@ pub async fn dispatch_interaction<'a, U, E>(
         interaction,
         command,
         has_sent_initial_response,
+        is_deferred,
     };
 
-    // Make sure that user has required permissions
-    if !super::check_required_permissions_and_owners_only(
-        crate::Context::Slash(ctx),
-        command.options.required_permissions,
-        command.options.owners_only,
-    )
-    .await
-    {
-        (this.options.slash_options.missing_permissions_handler)(ctx).await;
+    // Discord only knows about the top-level flag, so check the whole chain
+    if chain.iter().any(|(_, cmd)| cmd.options.guild_only) && interaction.guild_id.is_none() {
+        (this.options.slash_options.guild_only_handler)(ctx).await;
         return Ok(());
     }
 
-    // Only continue if command check returns true
-    let command_check = command
-        .options
-        .check
-        .unwrap_or(this.options.slash_options.command_check);
-    let check_passes = command_check(ctx).await.map_err(|e| {
-        (
-            e,
+    // The required permissions and checks of the command and all its parent commands must pass
+    for &(_, chain_command) in &chain {
+        // Make sure that user has required permissions
+        if !super::check_required_permissions_and_owners_only(
+            crate::Context::Slash(ctx),
+            chain_command.options.required_permissions,
+            chain_command.options.owners_only,
+        )
+        .await
+        {
+            (this.options.slash_options.missing_permissions_handler)(ctx).await;
+            return Ok(());
+        }
+
+        // Only continue if command check returns true
+        let command_check = chain_command
+            .options
+            .check
+            .unwrap_or(this.options.slash_options.command_check);
+        let check_passes = command_check(ctx).await.map_err(|e| {
+            (
+                crate::FrameworkError::User(e),
+                crate::SlashCommandErrorContext {
+                    command: chain_command,
+                    ctx,
+                    while_checking: true,
+                },
+            )
+        })?;
+        if !check_passes {
+            return Err((
+                crate::FrameworkError::CommandCheckFailed,
+                crate::SlashCommandErrorContext {
+                    command: chain_command,
+                    ctx,
+                    while_checking: true,
+                },
+            ));
+        }
+    }
+
+    let cooldown_ctx = crate::CooldownContext {
+        user_id: interaction.user.id,
+        guild_id: interaction.guild_id,
+        channel_id: interaction.channel_id,
+    };
+    if let Some(remaining_cooldown) =
+        this.trigger_cooldown(&qualified_name, &command.options.cooldowns, cooldown_ctx)
+    {
+        return Err((
+            crate::FrameworkError::CooldownHit { remaining_cooldown },
             crate::SlashCommandErrorContext {
                 command,
                 ctx,
-                while_checking: true,
+                while_checking: false,
             },
-        )
-    })?;
-    if !check_passes {
-        return Ok(());
+        ));
     }
 
-    if command
+    // Deferring is the slash command equivalent of broadcasting typing
+    let defer_delay = if command
         .options
         .defer_response
         .unwrap_or(this.options.slash_options.defer_response)
     {
-        if let Err(e) = ctx.defer_response().await {
-            println!("Failed to send interaction acknowledgement: {}", e);
+        Some(std::time::Duration::from_secs(0))
+    } else {
+        match &command.options.broadcast_typing {
+            None | Some(crate::BroadcastTypingBehavior::None) => None,
+            Some(crate::BroadcastTypingBehavior::Immediate) => {
+                Some(std::time::Duration::from_secs(0))
+            }
+            Some(crate::BroadcastTypingBehavior::WithDelay(delay)) => Some(*delay),
         }
-    }
+    };
+    let defer = |ctx: crate::SlashContext<'a, U, E>| async move {
+        if let Err(e) = ctx.defer_with(command.options.ephemeral).await {
+            let error_ctx = crate::SlashCommandErrorContext {
+                command,
+                ctx,
+                while_checking: false,
+            };
+            (this.options.on_error)(
+                crate::FrameworkError::Serenity(e),
+                crate::ErrorContext::Command(crate::CommandErrorContext::Slash(error_ctx)),
+            )
+            .await;
+        }
+    };
+    let defer_delay = match defer_delay {
+        Some(delay) if delay == std::time::Duration::from_secs(0) => {
+            defer(ctx).await;
+            None
+        }
+        delay => delay,
+    };
 
     (this.options.pre_command)(crate::Context::Slash(ctx)).await;
 
-    (command.action)(ctx, options).await.map_err(|e| {
+    let action = match command.action {
+        crate::SlashCommandAction::ChatInput(action) => action(ctx, options),
+        crate::SlashCommandAction::User(action) => {
+            let target_user = interaction
+                .data
+                .target_id
+                .and_then(|id| interaction.data.resolved.users.get(&serenity::UserId(id.0)));
+            match target_user {
+                Some(user) => action(ctx, user.clone()),
+                None => {
+                    println!("Warning: user context menu interaction without target user");
+                    return Ok(());
+                }
+            }
+        }
+        crate::SlashCommandAction::Message(action) => {
+            let target_message = interaction.data.target_id.and_then(|id| {
+                interaction
+                    .data
+                    .resolved
+                    .messages
+                    .get(&serenity::MessageId(id.0))
+            });
+            match target_message {
+                Some(message) => action(ctx, message.clone()),
+                None => {
+                    println!("Warning: message context menu interaction without target message");
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    // The action isn't polled while deferring, so it can't send a follow-up before the deferred
+    // response is through. A reply that's already underway claims the initial response, which
+    // turns the deferral into a no-op
+    let result = match defer_delay {
+        Some(delay) => {
+            let delay = Box::pin(tokio::time::sleep(delay));
+            match futures::future::select(action, delay).await {
+                futures::future::Either::Left((result, _)) => result,
+                futures::future::Either::Right(((), action)) => {
+                    defer(ctx).await;
+                    action.await
+                }
+            }
+        }
+        None => action.await,
+    };
+    result.map_err(|e| {
         (
             e,
             crate::SlashCommandErrorContext {
@@ -86,3 +259,251 @@ pub async fn dispatch_interaction<'a, U, E>(
         )
     })
 }
+
+/// Runs the autocomplete callback of the parameter that the user is currently typing and sends
+/// back the suggestions.
+///
+/// If the callback fails, an empty suggestion list is sent so the interaction doesn't go
+/// unanswered, and the error is returned. If sending the suggestions fails, that error is
+/// returned instead.
+pub async fn dispatch_autocomplete<'a, U, E>(
+    this: &'a super::Framework<U, E>,
+    ctx: &'a serenity::Context,
+    interaction: &'a serenity::AutocompleteInteraction,
+) -> Result<
+    (),
+    (
+        crate::FrameworkError<E>,
+        crate::AutocompleteErrorContext<'a, U, E>,
+    ),
+> {
+    let command = find_top_level_command(
+        &this.options.commands,
+        &interaction.data.name,
+        serenity::ApplicationCommandType::ChatInput,
+    );
+    let (command, options) = match command
+        .and_then(|cmd| find_leaf_command(cmd, &interaction.data.options))
+        .and_then(|(chain, options)| Some((chain.last()?.1, options)))
+    {
+        Some(x) => x,
+        None => {
+            println!(
+                "Warning: received autocomplete for unknown command \"{}\"",
+                interaction.data.name
+            );
+            return Ok(());
+        }
+    };
+
+    let focused_option = match options.iter().find(|option| option.focused) {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let (parameter_name, autocomplete_callback) = match command
+        .parameters
+        .iter()
+        .find(|param| param.name == focused_option.name)
+        .and_then(|param| Some((param.name, param.autocomplete_callback?)))
+    {
+        Some(x) => x,
+        None => {
+            println!(
+                "Warning: received autocomplete for parameter \"{}\" without callback",
+                focused_option.name
+            );
+            return Ok(());
+        }
+    };
+
+    // Integer and number parameters are autocompleted too, so don't assume a string
+    let partial_input = match &focused_option.value {
+        Some(serde_json::Value::String(x)) => x.clone(),
+        Some(x) => x.to_string(),
+        None => String::new(),
+    };
+
+    let autocomplete_ctx = crate::AutocompleteContext {
+        data: this.get_user_data().await,
+        discord: ctx,
+        framework: this,
+        interaction,
+        command,
+    };
+
+    let result = autocomplete_callback(autocomplete_ctx, &partial_input).await;
+    let choices = match &result {
+        Ok(choices) => choices
+            .iter()
+            .take(25)
+            .map(|choice| serde_json::json!({ "name": choice.name, "value": choice.value }))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    let response_result = interaction
+        .create_autocomplete_response(ctx, |f| {
+            f.0.insert("choices", serde_json::Value::Array(choices));
+            f
+        })
+        .await;
+
+    let result = match (result, response_result) {
+        (Err(e), _) => Err(crate::FrameworkError::User(e)),
+        (Ok(_), Err(e)) => Err(crate::FrameworkError::Serenity(e)),
+        (Ok(_), Ok(())) => Ok(()),
+    };
+    result.map_err(|e| {
+        (
+            e,
+            crate::AutocompleteErrorContext {
+                ctx: autocomplete_ctx,
+                parameter_name,
+                partial_input,
+            },
+        )
+    })
+}
+
+/// Runs the first component handler whose custom ID pattern matches the interaction. Does nothing
+/// if no handler matches, so that the interaction can be handled elsewhere, e.g. by a collector.
+pub async fn dispatch_component<'a, U, E>(
+    this: &'a super::Framework<U, E>,
+    ctx: &'a serenity::Context,
+    interaction: &'a serenity::MessageComponentInteraction,
+) -> Result<(), (crate::FrameworkError<E>, crate::ComponentContext<'a, U, E>)> {
+    let handler = match this.options.component_handlers.iter().find(|handler| {
+        handler
+            .match_custom_id(&interaction.data.custom_id)
+            .is_some()
+    }) {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+
+    let component_ctx = crate::ComponentContext {
+        data: this.get_user_data().await,
+        discord: ctx,
+        framework: this,
+        interaction,
+        handler,
+    };
+
+    (handler.action)(component_ctx)
+        .await
+        .map_err(|e| (crate::FrameworkError::User(e), component_ctx))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn command(
+        name: &'static str,
+        subcommands: Vec<crate::Command<(), ()>>,
+    ) -> crate::Command<(), ()> {
+        crate::Command {
+            name,
+            category: None,
+            subcommands,
+            prefix_action: None,
+            slash_action: Some(crate::SlashCommand {
+                name,
+                description: "",
+                action: crate::SlashCommandAction::ChatInput(|_, _| Box::pin(async { Ok(()) })),
+                parameters: Vec::new(),
+                options: Default::default(),
+                name_localizations: Default::default(),
+                description_localizations: Default::default(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_find_leaf_command() {
+        let config = command(
+            "config",
+            vec![command(
+                "prefix",
+                vec![command("set", vec![]), command("reset", vec![])],
+            )],
+        );
+
+        let options: Vec<serenity::ApplicationCommandInteractionDataOption> =
+            serde_json::from_value(serde_json::json!([{
+                "name": "prefix",
+                "type": 2,
+                "options": [{
+                    "name": "set",
+                    "type": 1,
+                    "options": [{ "name": "value", "type": 3, "value": "!" }],
+                }],
+            }]))
+            .unwrap();
+        let (chain, args) = find_leaf_command(&config, &options).unwrap();
+        let names: Vec<_> = chain.iter().map(|(_, cmd)| cmd.name).collect();
+        assert_eq!(names, ["config", "prefix", "set"]);
+        assert_eq!(args.len(), 1);
+        assert_eq!(args[0].name, "value");
+
+        let options: Vec<serenity::ApplicationCommandInteractionDataOption> =
+            serde_json::from_value(serde_json::json!([{
+                "name": "prefix",
+                "type": 2,
+                "options": [{ "name": "unknown", "type": 1, "options": [] }],
+            }]))
+            .unwrap();
+        assert!(find_leaf_command(&config, &options).is_none());
+
+        let flat = command("ping", vec![]);
+        let options: Vec<serenity::ApplicationCommandInteractionDataOption> =
+            serde_json::from_value(
+                serde_json::json!([{ "name": "value", "type": 3, "value": "x" }]),
+            )
+            .unwrap();
+        let (chain, args) = find_leaf_command(&flat, &options).unwrap();
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].1.name, "ping");
+        assert_eq!(args.len(), 1);
+    }
+
+    #[test]
+    fn test_find_leaf_command_keeps_parent_restrictions() {
+        let mut config = command("config", vec![command("set", vec![])]);
+        let parent_options = &mut config.slash_action.as_mut().unwrap().options;
+        parent_options.owners_only = true;
+        parent_options.required_permissions = serenity::Permissions::ADMINISTRATOR;
+
+        let options: Vec<serenity::ApplicationCommandInteractionDataOption> =
+            serde_json::from_value(serde_json::json!([{
+                "name": "set",
+                "type": 1,
+                "options": [],
+            }]))
+            .unwrap();
+        let (chain, _) = find_leaf_command(&config, &options).unwrap();
+
+        // Only the parent is restricted, but its restrictions must still be checked
+        let leaf = chain.last().unwrap().1;
+        assert_eq!(leaf.name, "set");
+        assert!(!leaf.options.owners_only);
+        assert!(chain.iter().any(|(_, cmd)| cmd.options.owners_only));
+        assert!(chain.iter().any(|(_, cmd)| cmd
+            .options
+            .required_permissions
+            .contains(serenity::Permissions::ADMINISTRATOR)));
+    }
+
+    #[test]
+    fn test_component_handler_match() {
+        let handler = |custom_id| crate::ComponentHandler::<(), ()> {
+            custom_id,
+            action: |_| Box::pin(async { Ok(()) }),
+        };
+
+        assert_eq!(handler("vote:*").match_custom_id("vote:42"), Some("42"));
+        assert_eq!(handler("vote:*").match_custom_id("vote:"), Some(""));
+        assert_eq!(handler("vote:*").match_custom_id("voting:42"), None);
+        assert_eq!(handler("cancel").match_custom_id("cancel"), Some(""));
+        assert_eq!(handler("cancel").match_custom_id("cancel:1"), None);
+    }
+}
@@ -19,6 +19,9 @@ pub use framework::*;
 mod reply;
 pub use reply::*;
 
+mod cooldown;
+pub use cooldown::*;
+
 pub mod defaults;
 
 pub use async_trait::async_trait;
@@ -39,10 +42,13 @@ pub mod serenity_prelude {
         builder::*,
         client::bridge::gateway::*,
         client::*,
+        collector::*,
         http::*,
         model::{
             event::*,
-            interactions::{application_command::*, *},
+            interactions::{
+                application_command::*, autocomplete::*, message_component::*, modal::*, *,
+            },
             prelude::*,
         },
         prelude::*,
@@ -0,0 +1,49 @@
+//! Per-guild prefix storage
+
+use crate::serenity_prelude as serenity;
+
+/// Looks up the prefix for a given guild. Set [`crate::PrefixFrameworkOptions::prefix_store`] to
+/// use it.
+///
+/// The stored prefix is tried before the [main prefix](crate::PrefixFrameworkOptions::prefix).
+/// If it doesn't match or there is none, the framework falls back to the main prefix,
+/// [`crate::PrefixFrameworkOptions::additional_prefixes`] and the mention prefix as usual.
+#[async_trait::async_trait]
+pub trait PrefixStore<U>: Send + Sync {
+    /// Returns the prefix for the given guild, or None to use the default prefixes only.
+    ///
+    /// `guild_id` is None in DMs. `data` is the framework user data, for stores that are backed
+    /// by something in there, like a database connection pool.
+    async fn get(&self, guild_id: Option<serenity::GuildId>, data: &U) -> Option<String>;
+}
+
+/// Simple [`PrefixStore`] which keeps the per-guild prefixes in memory.
+///
+/// Prefixes are lost on restart; use a custom [`PrefixStore`] implementation to persist them.
+#[derive(Debug, Default)]
+pub struct InMemoryPrefixStore {
+    prefixes: parking_lot::RwLock<std::collections::HashMap<serenity::GuildId, String>>,
+}
+
+impl InMemoryPrefixStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the prefix for the given guild, returning the previous one if there was any
+    pub fn set(&self, guild_id: serenity::GuildId, prefix: impl Into<String>) -> Option<String> {
+        self.prefixes.write().insert(guild_id, prefix.into())
+    }
+
+    /// Removes the custom prefix of the given guild, returning it if there was any
+    pub fn remove(&self, guild_id: serenity::GuildId) -> Option<String> {
+        self.prefixes.write().remove(&guild_id)
+    }
+}
+
+#[async_trait::async_trait]
+impl<U: Sync> PrefixStore<U> for InMemoryPrefixStore {
+    async fn get(&self, guild_id: Option<serenity::GuildId>, _: &U) -> Option<String> {
+        self.prefixes.read().get(&guild_id?).cloned()
+    }
+}
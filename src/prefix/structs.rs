@@ -8,6 +8,15 @@ use crate::{serenity_prelude as serenity, BoxFuture, Framework};
 pub struct PrefixContext<'a, U, E> {
     pub discord: &'a serenity::Context,
     pub msg: &'a serenity::Message,
+    /// The prefix that triggered this command invocation, as it appears in the message. For
+    /// mention prefixes, this is the raw mention, e.g. `<@!123456789>`
+    pub prefix: &'a str,
+    /// The command name or alias that was used to invoke the command, as it appears in the
+    /// message
+    pub invoked_command_name: &'a str,
+    /// Everything after the command name, as it appears in the message. Unlike the arguments
+    /// passed to the command action, leading whitespace and newlines are preserved
+    pub raw_args: &'a str,
     pub framework: &'a Framework<U, E>,
     // Option, because otherwise you can't use this struct in a context where there is no command
     // Example: Etternabot's message listener
@@ -26,26 +35,59 @@ impl<U, E> crate::_GetGenerics for PrefixContext<'_, U, E> {
     type E = E;
 }
 
+impl<U, E> PrefixContext<'_, U, E> {
+    /// Returns the guild member of the message author, looked up in the cache or, failing that,
+    /// fetched via HTTP. Returns None outside of guilds or if the member couldn't be retrieved.
+    pub async fn author_member(&self) -> Option<std::borrow::Cow<'_, serenity::Member>> {
+        let guild_id = self.msg.guild_id?;
+        // The member isn't cached if the guild members intent is disabled
+        let member = match self.discord.cache.member(guild_id, self.msg.author.id) {
+            Some(member) => member,
+            None => guild_id
+                .member(self.discord, self.msg.author.id)
+                .await
+                .ok()?,
+        };
+        Some(std::borrow::Cow::Owned(member))
+    }
+}
+
 /// Optional settings for a [`PrefixCommand`].
 pub struct PrefixCommandOptions<U, E> {
     /// Short description of the command. Displayed inline in help menus and similar.
-    pub inline_help: Option<&'static str>,
+    pub inline_help: Option<std::borrow::Cow<'static, str>>,
     /// Multiline description with detailed usage instructions. Displayed in the command specific
     /// help: `~help command_name`
-    // TODO: fix the inconsistency that this is String and everywhere else it's &'static str
-    pub multiline_help: Option<fn() -> String>,
+    pub multiline_help: Option<std::borrow::Cow<'static, str>>,
+    /// Example invocations of the command, which help commands can display, e.g. `~add 1 2`
+    pub examples: Vec<std::borrow::Cow<'static, str>>,
     /// Alternative triggers for the command
     pub aliases: &'static [&'static str],
     /// Falls back to the framework-specified value on None. See there for documentation.
-    pub on_error: Option<fn(E, PrefixCommandErrorContext<'_, U, E>) -> BoxFuture<'_, ()>>,
+    pub on_error: Option<
+        fn(crate::FrameworkError<E>, PrefixCommandErrorContext<'_, U, E>) -> BoxFuture<'_, ()>,
+    >,
     /// If this function returns false, this command will not be executed.
+    ///
+    /// Runs after the [global check](PrefixFrameworkOptions::command_check) and
+    /// [category check](PrefixFrameworkOptions::category_checks).
     pub check: Option<fn(PrefixContext<'_, U, E>) -> BoxFuture<'_, Result<bool, E>>>,
     /// Whether to enable edit tracking for commands by default.
     ///
     /// Note: this won't do anything if `Framework::edit_tracker` isn't set.
     pub track_edits: bool,
+    /// Whether re-runs of an edit tracked command edit the previous response. If false, every
+    /// run sends a new message, e.g. for commands like `~roll` where history matters.
+    ///
+    /// Can be overridden per reply with [`crate::CreateReply::reuse_response`].
+    pub reuse_response: bool,
     /// Falls back to the framework-specified value on None. See there for documentation.
     pub broadcast_typing: Option<BroadcastTypingBehavior>,
+    /// Whether replies of this command are Discord replies to the invocation message. Falls back
+    /// to [`PrefixFrameworkOptions::reply_to_invocation`] on None.
+    ///
+    /// Can be overridden per reply with [`crate::CreateReply::reply`].
+    pub reply_to_invocation: Option<ReplyBehavior>,
     /// Whether to hide this command in help menus.
     pub hide_in_help: bool,
     /// Permissions which users must have to invoke this command.
@@ -55,6 +97,23 @@ pub struct PrefixCommandOptions<U, E> {
     /// If true, only users from the [owners list](crate::FrameworkOptions::owners) may use this
     /// command.
     pub owners_only: bool,
+    /// If true, this command is ignored when invoked outside of guilds, e.g. in DMs.
+    pub guild_only: bool,
+    /// Cooldowns of this command. They are shared with the slash implementation of the same name.
+    pub cooldowns: crate::CooldownConfig,
+    /// If true, this command can't be invoked on its own, only via one of its subcommands.
+    ///
+    /// When invoked without a valid subcommand, [`PrefixFrameworkOptions::unknown_command`] is
+    /// called with this command in [`PrefixContext::command`], for example to list the valid
+    /// subcommands.
+    pub subcommand_required: bool,
+    /// If true, this command is only invocable as a slash command. The prefix dispatcher ignores
+    /// it, but help menus still list it.
+    pub slash_only: bool,
+    /// Arbitrary data attached to this command, for example a cooldown bucket name or an
+    /// analytics tag. Can be read in checks and [`crate::FrameworkOptions::pre_command`] via
+    /// [`PrefixContext::command`] and [`std::any::Any::downcast_ref`].
+    pub custom_data: Box<dyn std::any::Any + Send + Sync>,
 }
 
 impl<U, E> Default for PrefixCommandOptions<U, E> {
@@ -62,14 +121,22 @@ impl<U, E> Default for PrefixCommandOptions<U, E> {
         Self {
             inline_help: None,
             multiline_help: None,
+            examples: Vec::new(),
             check: None,
             on_error: None,
             aliases: &[],
             track_edits: false,
+            reuse_response: true,
             broadcast_typing: None,
+            reply_to_invocation: None,
             hide_in_help: false,
             required_permissions: serenity::Permissions::empty(),
             owners_only: false,
+            guild_only: false,
+            cooldowns: Default::default(),
+            subcommand_required: false,
+            slash_only: false,
+            custom_data: Box::new(()),
         }
     }
 }
@@ -80,21 +147,14 @@ pub struct PrefixCommand<U, E> {
     /// Main name of the command. Aliases can be set in [`PrefixCommandOptions::aliases`].
     pub name: &'static str,
     /// Callback to execute when this command is invoked.
-    pub action: for<'a> fn(PrefixContext<'a, U, E>, args: &'a str) -> BoxFuture<'a, Result<(), E>>,
+    pub action: for<'a> fn(
+        PrefixContext<'a, U, E>,
+        args: &'a str,
+    ) -> BoxFuture<'a, Result<(), crate::FrameworkError<E>>>,
     /// Optional data to change this command's behavior.
     pub options: PrefixCommandOptions<U, E>,
 }
 
-/// Includes a command, plus metadata like associated sub-commands or category.
-pub struct PrefixCommandMeta<U, E> {
-    /// Core command data
-    pub command: PrefixCommand<U, E>,
-    /// Identifier for the category that this command will be displayed in for help commands.
-    pub category: Option<&'static str>,
-    /// Possible subcommands
-    pub subcommands: Vec<PrefixCommandMeta<U, E>>,
-}
-
 /// Context passed alongside the error value to error handlers
 pub struct PrefixCommandErrorContext<'a, U, E> {
     /// Whether the error occured in a [`check`](PrefixCommandOptions::check) callback
@@ -116,49 +176,168 @@ impl<U, E> Clone for PrefixCommandErrorContext<'_, U, E> {
 }
 
 pub enum Prefix {
-    /// A case-sensitive string literal prefix (passed to [`str::strip_prefix`])
-    Literal(&'static str),
+    /// A string literal prefix. Case-sensitive unless
+    /// [`PrefixFrameworkOptions::case_insensitive_prefixes`] is set
+    ///
+    /// Can hold both string literals and runtime strings: `Prefix::Literal("!".into())` or
+    /// `Prefix::Literal(prefix_from_config.into())`
+    Literal(std::borrow::Cow<'static, str>),
     /// Regular expression which matches the prefix
     Regex(regex::Regex),
 }
 
 pub struct PrefixFrameworkOptions<U, E> {
-    /// List of bot commands.
-    pub commands: Vec<PrefixCommandMeta<U, E>>,
+    /// Invoked for every message before any prefix or command processing. If it returns false,
+    /// the message is silently ignored.
+    ///
+    /// Useful to ignore entire channels or to implement user blacklists, for example by looking
+    /// up the message's channel or author in the cache.
+    pub message_filter:
+        Option<for<'a> fn(&'a serenity::Context, &'a serenity::Message) -> BoxFuture<'a, bool>>,
+    /// The main bot prefix. Can be set to None if the bot supports only
+    /// [dynamic prefixes](Self::dynamic_prefix) or is slash-only.
+    pub prefix: Option<String>,
     /// List of additional bot prefixes
     // TODO: maybe it would be nicer to have separate fields for literal and regex prefixes
     // That way, you don't need to wrap every single literal prefix in a long path which looks ugly
     pub additional_prefixes: Vec<Prefix>,
+    /// Callback invoked on every message to return candidate prefixes.
+    ///
+    /// Override this field for dynamic prefixes which change depending on guild or user, for
+    /// example prefixes looked up from a database.
+    ///
+    /// Return the prefixes as owned Strings; the framework tries each in order and strips the
+    /// first matching one off the message. If none match, the framework falls back to the
+    /// [main prefix](Self::prefix), [`Self::additional_prefixes`] and the mention prefix.
+    ///
+    /// Errors, for example from a failed database lookup, are passed to
+    /// [`crate::FrameworkOptions::on_error`] with [`crate::ErrorContext::DynamicPrefix`] and the
+    /// message is not processed further.
+    pub dynamic_prefix: Option<
+        for<'a> fn(
+            &'a serenity::Context,
+            &'a serenity::Message,
+            &'a U,
+        ) -> BoxFuture<'a, Result<Vec<String>, E>>,
+    >,
+    /// Per-guild prefix lookup, for example [`crate::InMemoryPrefixStore`].
+    ///
+    /// Consulted after [`Self::dynamic_prefix`] and before the [main prefix](Self::prefix).
+    pub prefix_store: Option<Box<dyn crate::PrefixStore<U>>>,
     /// Callback invoked on every message to strip the prefix off an incoming message.
     ///
-    /// Override this field for dynamic prefixes which change depending on guild or user.
+    /// Override this field for advanced dynamic prefixes which can't be expressed as a plain
+    /// string. Consider using [`Self::dynamic_prefix`] instead if possible.
     ///
     /// As return value, use the message content with the prefix stripped: ```rust
     /// msg.content.strip_prefix(my_cool_prefix)
     /// ```
-    pub dynamic_prefix: Option<
+    ///
+    /// Errors are handled like in [`Self::dynamic_prefix`].
+    pub stripped_dynamic_prefix: Option<
         for<'a> fn(
             &'a serenity::Context,
             &'a serenity::Message,
             &'a U,
-        ) -> BoxFuture<'a, Option<&'a str>>,
+        ) -> BoxFuture<'a, Result<Option<&'a str>, E>>,
     >,
     /// Treat a bot mention (a ping) like a prefix
     pub mention_as_prefix: bool,
+    /// If true, text directly following the bot mention without whitespace (`@bot help me`
+    /// without a space) is not treated as a command. A bare mention still matches.
+    pub require_space_after_mention: bool,
+    /// Whether to allow whitespace between the prefix and the command name, e.g. `! help`.
+    ///
+    /// Applies to all kinds of prefixes. Whitespace after a mention prefix is always allowed.
+    ///
+    /// Defaults to true.
+    pub ignore_whitespace_after_prefix: bool,
     /// Provide a callback to be invoked before every command. The command will only be executed
     /// if the callback returns true.
     ///
-    /// Individual commands may override this callback.
+    /// Runs before [category checks](Self::category_checks) and the command's own
+    /// [check](PrefixCommandOptions::check).
     pub command_check: fn(PrefixContext<'_, U, E>) -> BoxFuture<'_, Result<bool, E>>,
+    /// Checks that apply to all commands of a [category](crate::Command::category), keyed by
+    /// category name. The command will only be executed if the callback returns true.
+    ///
+    /// Runs after [`Self::command_check`] and before the command's own
+    /// [check](PrefixCommandOptions::check).
+    pub category_checks: std::collections::HashMap<
+        &'static str,
+        fn(PrefixContext<'_, U, E>) -> BoxFuture<'_, Result<bool, E>>,
+    >,
+    /// Invoked when a message starts with a prefix, but no command matches the command name.
+    ///
+    /// Receives a context without command, the attempted command name and the remaining
+    /// arguments.
+    ///
+    /// Also invoked when a command with [`PrefixCommandOptions::subcommand_required`] is invoked
+    /// without a valid subcommand. In that case, the context contains the parent command and the
+    /// attempted subcommand name is passed. Errors are passed to [`crate::FrameworkOptions::on_error`] with
+    /// [`crate::ErrorContext::UnknownCommand`].
+    pub unknown_command: Option<
+        for<'a> fn(PrefixContext<'a, U, E>, &'a str, &'a str) -> BoxFuture<'a, Result<(), E>>,
+    >,
     /// If Some, the framework will react to message edits by editing the corresponding bot response
     /// with the new result.
     pub edit_tracker: Option<parking_lot::RwLock<super::EditTracker>>,
+    /// Whether to delete the bot response when the user deletes their command invocation message.
+    ///
+    /// Only has an effect if [`Self::edit_tracker`] is set. Either way, the message is removed
+    /// from the edit tracker.
+    pub delete_response_on_invocation_delete: bool,
+    /// If true, edited messages which are not (or no longer) in the edit tracker cache are
+    /// executed as fresh command invocations, and the bot sends a new response.
+    ///
+    /// Only has an effect if [`Self::edit_tracker`] is set.
+    pub execute_untracked_edits: bool,
+    /// If true, message edits are ignored while the command invoked by the original message is
+    /// still running and hasn't responded yet. Prevents the same command from running twice
+    /// concurrently.
+    ///
+    /// Only has an effect if [`Self::edit_tracker`] is set.
+    pub ignore_edits_if_not_yet_responded: bool,
+    /// If true, commands are re-run on message updates which don't change the content, for
+    /// example when Discord resolves a link embed or the message is pinned.
+    ///
+    /// Only has an effect if [`Self::edit_tracker`] is set.
+    pub rerun_on_noop_edit: bool,
     /// Whether to broadcast a typing indicator while executing this commmand's action.
     pub broadcast_typing: BroadcastTypingBehavior,
+    /// How to emulate [ephemeral](crate::CreateReply::ephemeral) replies in prefix commands,
+    /// where Discord doesn't support them.
+    pub ephemeral_behavior: PrefixEphemeralBehavior,
+    /// Whether command replies are Discord replies to the invocation message, unless the command
+    /// or the reply itself says otherwise.
+    ///
+    /// The most specific setting wins: [`crate::CreateReply::reply`] over
+    /// [`PrefixCommandOptions::reply_to_invocation`] over this.
+    pub reply_to_invocation: ReplyBehavior,
     /// Whether commands in messages emitted by the bot itself should be executed as well.
     pub execute_self_messages: bool,
+    /// If true, commands are silently skipped if the bot itself doesn't have permission to send
+    /// messages in the channel, instead of failing later with an HTTP error when replying.
+    ///
+    /// The bot's permissions are always sufficient in DMs.
+    pub require_send_permissions: bool,
+    /// If true, messages in DMs which don't start with a prefix are still checked for commands,
+    /// so that e.g. `help` works without prefix. [`PrefixContext::prefix`] is empty in that case.
+    ///
+    /// [`Self::unknown_command`] is not called for such messages, because most of them are
+    /// ordinary conversation.
+    pub execute_prefixless_in_dms: bool,
+    /// Whether to ignore messages from other bots and webhooks.
+    ///
+    /// The bot's own messages are controlled by [`Self::execute_self_messages`] instead.
+    pub ignore_bots: bool,
     /// Whether command names should be compared case-insensitively.
     pub case_insensitive_commands: bool,
+    /// Whether the main prefix, literal [`Self::additional_prefixes`] and prefixes returned by
+    /// [`Self::dynamic_prefix`] should be compared case-insensitively.
+    ///
+    /// Only ASCII characters are compared case-insensitively.
+    pub case_insensitive_prefixes: bool,
     /* // STUB: implement
     /// Whether to invoke help command when someone sends a message with just a bot mention
     pub help_when_mentioned: bool,
@@ -173,15 +352,32 @@ pub struct PrefixFrameworkOptions<U, E> {
 impl<U, E> Default for PrefixFrameworkOptions<U, E> {
     fn default() -> Self {
         Self {
-            commands: Vec::new(),
+            message_filter: None,
+            prefix: None,
             additional_prefixes: Vec::new(),
             dynamic_prefix: None,
+            prefix_store: None,
+            stripped_dynamic_prefix: None,
             mention_as_prefix: true,
+            require_space_after_mention: false,
+            ignore_whitespace_after_prefix: true,
             command_check: |_| Box::pin(async { Ok(true) }),
+            category_checks: std::collections::HashMap::new(),
+            unknown_command: None,
             edit_tracker: None,
+            delete_response_on_invocation_delete: true,
+            execute_untracked_edits: true,
+            ignore_edits_if_not_yet_responded: false,
+            rerun_on_noop_edit: false,
             broadcast_typing: BroadcastTypingBehavior::None,
+            ephemeral_behavior: PrefixEphemeralBehavior::Ignore,
+            reply_to_invocation: ReplyBehavior::NoReply,
             execute_self_messages: false,
+            execute_prefixless_in_dms: false,
+            ignore_bots: true,
+            require_send_permissions: false,
             case_insensitive_commands: true,
+            case_insensitive_prefixes: false,
             // help_when_mentioned: true,
             // help_commmand: None,
             // command_specific_help_commmand: None,
@@ -189,12 +385,42 @@ impl<U, E> Default for PrefixFrameworkOptions<U, E> {
     }
 }
 
+/// Whether and when to broadcast a typing indicator while a command runs.
+///
+/// Typing is stopped as soon as the command sends its first response.
 pub enum BroadcastTypingBehavior {
     /// Don't broadcast typing
     None,
-    // TODO: make Immediate variant maybe?
+    /// Broadcast typing immediately when the command starts
+    Immediate,
     /// Broadcast typing after the command has been running for a certain time
-    ///
-    /// Set duration to zero for immediate typing broadcast
     WithDelay(std::time::Duration),
 }
+
+/// What to do with prefix command replies that were requested to be
+/// [ephemeral](crate::CreateReply::ephemeral)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixEphemeralBehavior {
+    /// Send them as normal messages
+    Ignore,
+    /// Delete them after the given delay. Only newly sent responses are deleted, not existing
+    /// responses which were edited, e.g. by re-runs of edit tracked commands.
+    Delete {
+        after: std::time::Duration,
+        /// Whether to delete the invocation message as well. Requires the Manage Messages
+        /// permission in guilds.
+        delete_invocation: bool,
+    },
+}
+
+/// Whether a prefix command response is sent as a Discord reply to the invocation message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyBehavior {
+    /// Send a regular message
+    NoReply,
+    /// Reply to the invocation message. Whether its author is pinged depends on the allowed
+    /// mentions, see [`serenity::CreateAllowedMentions::replied_user`].
+    Reply,
+    /// Reply to the invocation message without pinging its author. Other mentions are unaffected.
+    ReplyWithoutPing,
+}
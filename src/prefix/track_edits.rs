@@ -3,126 +3,733 @@
 
 use crate::serenity_prelude as serenity;
 
-fn update_message(message: &mut serenity::Message, update: serenity::MessageUpdateEvent) {
+fn update_message(message: &mut serenity::Message, update: &serenity::MessageUpdateEvent) {
     message.id = update.id;
     message.channel_id = update.channel_id;
     message.guild_id = update.guild_id;
 
-    if let Some(kind) = update.kind {
-        message.kind = kind;
+    if let Some(kind) = &update.kind {
+        message.kind = *kind;
     }
-    if let Some(content) = update.content {
-        message.content = content;
+    if let Some(content) = &update.content {
+        message.content = content.clone();
     }
-    if let Some(tts) = update.tts {
-        message.tts = tts;
+    if let Some(tts) = &update.tts {
+        message.tts = *tts;
     }
-    if let Some(pinned) = update.pinned {
-        message.pinned = pinned;
+    if let Some(pinned) = &update.pinned {
+        message.pinned = *pinned;
     }
-    if let Some(timestamp) = update.timestamp {
-        message.timestamp = timestamp;
+    if let Some(timestamp) = &update.timestamp {
+        message.timestamp = *timestamp;
     }
-    if let Some(edited_timestamp) = update.edited_timestamp {
-        message.edited_timestamp = Some(edited_timestamp);
+    if let Some(edited_timestamp) = &update.edited_timestamp {
+        message.edited_timestamp = Some(*edited_timestamp);
     }
-    if let Some(author) = update.author {
-        message.author = author;
+    if let Some(author) = &update.author {
+        message.author = author.clone();
     }
-    if let Some(mention_everyone) = update.mention_everyone {
-        message.mention_everyone = mention_everyone;
+    if let Some(mention_everyone) = &update.mention_everyone {
+        message.mention_everyone = *mention_everyone;
     }
-    if let Some(mentions) = update.mentions {
-        message.mentions = mentions;
+    if let Some(mentions) = &update.mentions {
+        message.mentions = mentions.clone();
     }
-    if let Some(mention_roles) = update.mention_roles {
-        message.mention_roles = mention_roles;
+    if let Some(mention_roles) = &update.mention_roles {
+        message.mention_roles = mention_roles.clone();
     }
-    if let Some(attachments) = update.attachments {
-        message.attachments = attachments;
+    if let Some(mention_channels) = &update.mention_channels {
+        message.mention_channels = mention_channels.clone();
+    }
+    if let Some(attachments) = &update.attachments {
+        message.attachments = attachments.clone();
+    }
+    if let Some(embeds) = &update.embeds {
+        // The update event contains the embeds as raw JSON values; skip malformed ones
+        message.embeds = embeds
+            .iter()
+            .filter_map(|embed| serde_json::from_value(embed.clone()).ok())
+            .collect();
+    }
+    if let Some(reactions) = &update.reactions {
+        message.reactions = reactions.clone();
+    }
+    if let Some(webhook_id) = &update.webhook_id {
+        message.webhook_id = Some(*webhook_id);
+    }
+    if let Some(activity) = &update.activity {
+        message.activity = Some(activity.clone());
+    }
+    if let Some(application) = &update.application {
+        message.application = Some(application.clone());
+    }
+    if let Some(message_reference) = &update.message_reference {
+        message.message_reference = Some(message_reference.clone());
+    }
+    if let Some(flags) = &update.flags {
+        message.flags = Some(*flags);
+    }
+    if let Some(stickers) = &update.stickers {
+        message.stickers = stickers.clone();
+    }
+    if let Some(referenced_message) = &update.referenced_message {
+        message.referenced_message = referenced_message.clone();
+    }
+    if let Some(components) = &update.components {
+        message.components = components.clone();
+    }
+}
+
+/// Configuration for an [`EditTracker`], passed to [`EditTracker::with_settings`]
+/// Lower bound for [`EditTrackerSettings::purge_interval`], so that the purge task doesn't spin
+const MIN_PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+#[derive(Clone, Debug)]
+pub struct EditTrackerSettings {
+    /// How long after the last update a command invocation is tracked
+    pub max_duration: std::time::Duration,
+    /// How many command invocations are tracked at most. When exceeded, the least recently
+    /// updated invocation is forgotten
+    pub max_entries: usize,
+    /// How often expired invocations are purged from the cache. Clamped to at least one
+    /// second
+    pub purge_interval: std::time::Duration,
+}
+
+impl Default for EditTrackerSettings {
+    fn default() -> Self {
+        Self {
+            max_duration: std::time::Duration::from_secs(3600),
+            max_entries: usize::MAX,
+            purge_interval: std::time::Duration::from_secs(60),
+        }
+    }
+}
+
+/// Applies a message update event to a message, or to an empty message if the previous version
+/// isn't known. Fields not contained in the update are kept.
+pub fn apply_message_update(
+    message: Option<serenity::Message>,
+    update: &serenity::MessageUpdateEvent,
+) -> serenity::Message {
+    let mut message = message.unwrap_or_else(|| serenity::CustomMessage::new().build());
+    update_message(&mut message, update);
+    message
+}
+
+/// A bot response of a tracked command invocation
+#[derive(Clone, Debug)]
+pub struct TrackedResponse {
+    id: serenity::MessageId,
+    channel_id: serenity::ChannelId,
+    /// Hashes of the file names and contents of the attachments, to detect unchanged attachments
+    /// on edit. None for attachments whose contents are unknown, e.g. of responses registered via
+    /// [`EditTracker::register_response`]
+    attachment_hashes: Vec<Option<u64>>,
+}
+
+impl TrackedResponse {
+    fn new(message: &serenity::Message) -> Self {
+        Self {
+            id: message.id,
+            channel_id: message.channel_id,
+            attachment_hashes: vec![None; message.attachments.len()],
+        }
+    }
+
+    pub fn id(&self) -> serenity::MessageId {
+        self.id
+    }
+
+    pub fn channel_id(&self) -> serenity::ChannelId {
+        self.channel_id
+    }
+}
+
+/// A tracked command invocation: the user message and the bot responses to it.
+///
+/// Only the data needed for edit tracking is stored, not the full messages. Of the user message,
+/// the author and content are kept to rebuild the message from update events that don't contain
+/// them, like resolved link embeds.
+///
+/// Obtained via [`EditTracker::get`] and [`EditTracker::iter`].
+pub struct TrackedInvocation {
+    user_msg_id: serenity::MessageId,
+    channel_id: serenity::ChannelId,
+    /// Creation or last edit timestamp of the user message
+    last_edit: chrono::DateTime<chrono::Utc>,
+    /// Author of the user message
+    author: serenity::User,
+    /// Current content of the user message. Also used to detect updates that don't change it
+    content: String,
+    /// Bot responses in the order they were sent
+    bot_responses: Vec<TrackedResponse>,
+    /// How many responses the current run of the command has sent so far. When the command is
+    /// re-run, the nth response edits the nth entry in `bot_responses`
+    responses_in_current_run: usize,
+    /// Whether a run of the command is in progress, i.e. it was started with
+    /// [`EditTracker::track_command`] and hasn't been finished with
+    /// [`EditTracker::finish_command`] yet
+    running: bool,
+    /// Position in `EditTracker::recency`
+    recency_key: u64,
+}
+
+impl TrackedInvocation {
+    /// ID of the command invocation message
+    pub fn user_msg_id(&self) -> serenity::MessageId {
+        self.user_msg_id
+    }
+
+    /// Channel of the command invocation message
+    pub fn channel_id(&self) -> serenity::ChannelId {
+        self.channel_id
+    }
+
+    /// Creation or last edit timestamp of the command invocation message
+    pub fn last_edit(&self) -> chrono::DateTime<chrono::Utc> {
+        self.last_edit
+    }
+
+    /// The bot responses in the order they were sent
+    pub fn bot_responses(&self) -> &[TrackedResponse] {
+        &self.bot_responses
     }
-    // if let Some(embeds) = update.embeds {
-    //     message.embeds = embeds;
-    // }
 }
 
 pub struct EditTracker {
     max_duration: std::time::Duration,
-    cache: Vec<(serenity::Message, serenity::Message)>,
+    max_entries: usize,
+    purge_interval: std::time::Duration,
+    /// Tracked invocations, keyed by the user message ID
+    cache: std::collections::HashMap<serenity::MessageId, TrackedInvocation>,
+    /// User message IDs ordered from least to most recently updated, so that the oldest entries
+    /// can be evicted when `max_entries` is exceeded
+    recency: std::collections::BTreeMap<u64, serenity::MessageId>,
+    /// Counter to order updates in `recency`
+    next_recency_key: u64,
+    /// Maps bot response IDs to the ID of the user message they respond to
+    response_owners: std::collections::HashMap<serenity::MessageId, serenity::MessageId>,
 }
 
 impl EditTracker {
     pub fn for_timespan(duration: std::time::Duration) -> parking_lot::RwLock<Self> {
-        parking_lot::RwLock::new(Self {
+        Self::for_timespan_with_limit(duration, usize::MAX)
+    }
+
+    /// Like [`Self::for_timespan`], but tracks at most `max_entries` command invocations at once.
+    /// When the limit is exceeded, the least recently updated invocation is forgotten.
+    pub fn for_timespan_with_limit(
+        duration: std::time::Duration,
+        max_entries: usize,
+    ) -> parking_lot::RwLock<Self> {
+        Self::with_settings(EditTrackerSettings {
             max_duration: duration,
-            cache: Vec::new(),
+            max_entries,
+            // Short tracking windows shouldn't keep stale entries around for much longer
+            purge_interval: std::cmp::min(duration, EditTrackerSettings::default().purge_interval),
         })
     }
 
-    /// Returns a copy of a newly up-to-date cached message, or a brand new generated message when
-    /// not in cache
+    pub fn with_settings(settings: EditTrackerSettings) -> parking_lot::RwLock<Self> {
+        parking_lot::RwLock::new(Self {
+            max_duration: settings.max_duration,
+            max_entries: settings.max_entries,
+            purge_interval: std::cmp::max(settings.purge_interval, MIN_PURGE_INTERVAL),
+            cache: std::collections::HashMap::new(),
+            recency: std::collections::BTreeMap::new(),
+            next_recency_key: 0,
+            response_owners: std::collections::HashMap::new(),
+        })
+    }
+
+    /// How often the framework calls [`Self::purge`]
+    pub fn purge_interval(&self) -> std::time::Duration {
+        self.purge_interval
+    }
+
+    /// Marks the entry as most recently updated and returns it
+    fn touch(&mut self, user_msg_id: serenity::MessageId) -> Option<&mut TrackedInvocation> {
+        let invocation = self.cache.get_mut(&user_msg_id)?;
+        self.recency.remove(&invocation.recency_key);
+        invocation.recency_key = self.next_recency_key;
+        self.recency.insert(self.next_recency_key, user_msg_id);
+        self.next_recency_key += 1;
+        Some(invocation)
+    }
+
+    /// Adds an entry as most recently updated and evicts the least recently updated entries if
+    /// the cache is over capacity
+    fn insert(&mut self, user_msg: &serenity::Message, bot_responses: Vec<TrackedResponse>) {
+        self.remove(user_msg.id);
+
+        self.recency.insert(self.next_recency_key, user_msg.id);
+        for bot_response in &bot_responses {
+            self.response_owners.insert(bot_response.id, user_msg.id);
+        }
+        self.cache.insert(
+            user_msg.id,
+            TrackedInvocation {
+                user_msg_id: user_msg.id,
+                channel_id: user_msg.channel_id,
+                last_edit: user_msg.edited_timestamp.unwrap_or(user_msg.timestamp),
+                author: user_msg.author.clone(),
+                content: user_msg.content.clone(),
+                responses_in_current_run: bot_responses.len(),
+                bot_responses,
+                running: false,
+                recency_key: self.next_recency_key,
+            },
+        );
+        self.next_recency_key += 1;
+
+        while self.cache.len() > self.max_entries {
+            let oldest = match self.recency.values().next() {
+                Some(&oldest) => oldest,
+                None => break,
+            };
+            self.remove(oldest);
+        }
+    }
+
+    /// Returns a message reconstructed from the update event. For tracked messages, fields that
+    /// the update doesn't contain are filled in from the tracked author and content. Untracked
+    /// messages can only be reconstructed from updates that contain the author and content, so
+    /// None is returned for other updates.
+    ///
+    /// The returned boolean is true if the message was present in the cache.
+    ///
+    /// If `ignore_edits_if_not_yet_responded` is true, None is returned for messages whose
+    /// command is still running and hasn't responded yet.
+    ///
+    /// Unless `rerun_on_noop_edit` is true, None is also returned for updates which don't change
+    /// the message content, like resolved link embeds or pins.
     pub fn process_message_update(
         &mut self,
         user_msg_update: &serenity::MessageUpdateEvent,
-    ) -> serenity::Message {
-        match self
-            .cache
-            .iter_mut()
-            .find(|(user_msg, _)| user_msg.id == user_msg_update.id)
-        {
-            Some((user_msg, _)) => {
-                update_message(user_msg, user_msg_update.clone());
-                user_msg.clone()
+        ignore_edits_if_not_yet_responded: bool,
+        rerun_on_noop_edit: bool,
+    ) -> Option<(serenity::Message, bool)> {
+        match self.touch(user_msg_update.id) {
+            Some(invocation) => {
+                if ignore_edits_if_not_yet_responded
+                    && invocation.running
+                    && invocation.responses_in_current_run == 0
+                {
+                    return None;
+                }
+
+                let mut content_changed = false;
+                if let Some(content) = &user_msg_update.content {
+                    content_changed = *content != invocation.content;
+                    invocation.content = content.clone();
+                }
+                if let Some(author) = &user_msg_update.author {
+                    invocation.author = author.clone();
+                }
+                if let Some(edited_timestamp) = user_msg_update.edited_timestamp {
+                    invocation.last_edit = edited_timestamp;
+                }
+
+                if !content_changed && !rerun_on_noop_edit {
+                    return None;
+                }
+
+                let previous_msg = serenity::CustomMessage::new()
+                    .id(invocation.user_msg_id)
+                    .channel_id(invocation.channel_id)
+                    .author(invocation.author.clone())
+                    .content(invocation.content.clone())
+                    .build();
+                let user_msg = apply_message_update(Some(previous_msg), user_msg_update);
+                Some((user_msg, true))
             }
             None => {
-                let mut user_msg = serenity::CustomMessage::new().build();
-                update_message(&mut user_msg, user_msg_update.clone());
-                user_msg
+                // Without the previous message we can't tell whether the content changed, but
+                // updates without content certainly didn't change it. They also can't be
+                // turned into a usable message
+                if user_msg_update.content.is_none() || user_msg_update.author.is_none() {
+                    return None;
+                }
+
+                // Discord sends the full message on content edits
+                let user_msg = apply_message_update(None, user_msg_update);
+                Some((user_msg, false))
             }
         }
     }
 
     pub fn purge(&mut self) {
         let max_duration = self.max_duration;
-        self.cache.retain(|(user_msg, _)| {
-            let last_update = user_msg.edited_timestamp.unwrap_or(user_msg.timestamp);
-            if let Ok(age) = (chrono::Utc::now() - last_update).to_std() {
-                age < max_duration
-            } else {
-                false
+        let recency = &mut self.recency;
+        let response_owners = &mut self.response_owners;
+        self.cache.retain(|_, invocation| {
+            let keep = match (chrono::Utc::now() - invocation.last_edit).to_std() {
+                Ok(age) => age < max_duration,
+                Err(_) => false,
+            };
+            if !keep {
+                recency.remove(&invocation.recency_key);
+                for bot_response in &invocation.bot_responses {
+                    response_owners.remove(&bot_response.id);
+                }
             }
+            keep
         });
     }
 
-    pub fn find_bot_response(
+    /// Returns the tracked invocation of the given user message, if any
+    pub fn get(&self, user_msg_id: serenity::MessageId) -> Option<&TrackedInvocation> {
+        self.cache.get(&user_msg_id)
+    }
+
+    /// Iterates over all tracked invocations in arbitrary order
+    pub fn iter(&self) -> impl Iterator<Item = &TrackedInvocation> {
+        self.cache.values()
+    }
+
+    /// Number of tracked invocations
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Returns the first bot response to the given user message
+    pub fn find_bot_response(&self, user_msg_id: serenity::MessageId) -> Option<&TrackedResponse> {
+        self.cache.get(&user_msg_id)?.bot_responses.first()
+    }
+
+    /// Removes a user message and its bot responses from the cache, returning the entry if it
+    /// existed
+    pub fn remove(&mut self, user_msg_id: serenity::MessageId) -> Option<TrackedInvocation> {
+        let invocation = self.cache.remove(&user_msg_id)?;
+        self.recency.remove(&invocation.recency_key);
+        for bot_response in &invocation.bot_responses {
+            self.response_owners.remove(&bot_response.id);
+        }
+        Some(invocation)
+    }
+
+    /// Forgets a bot response, for example because it was deleted. The next run of the command
+    /// sends a new message in its place.
+    ///
+    /// Returns the ID of the user message the response belonged to, if it was tracked.
+    pub fn remove_bot_response(
+        &mut self,
+        bot_msg_id: serenity::MessageId,
+    ) -> Option<serenity::MessageId> {
+        let user_msg_id = self.response_owners.remove(&bot_msg_id)?;
+        if let Some(invocation) = self.cache.get_mut(&user_msg_id) {
+            if let Some(index) = invocation
+                .bot_responses
+                .iter()
+                .position(|response| response.id == bot_msg_id)
+            {
+                invocation.bot_responses.remove(index);
+                if index < invocation.responses_in_current_run {
+                    invocation.responses_in_current_run -= 1;
+                }
+            }
+        }
+        Some(user_msg_id)
+    }
+
+    /// Notify the edit tracker that a command invocation has started, but hasn't responded yet.
+    ///
+    /// If the message is already tracked, i.e. the command is re-run after an edit, the
+    /// responses of the new run will replace the existing ones in order.
+    pub(crate) fn track_command(&mut self, user_msg: &serenity::Message) {
+        if !self.cache.contains_key(&user_msg.id) {
+            self.insert(user_msg, Vec::new());
+        }
+        if let Some(invocation) = self.cache.get_mut(&user_msg.id) {
+            invocation.responses_in_current_run = 0;
+            invocation.running = true;
+        }
+    }
+
+    /// Notify the edit tracker that a command run has finished. Returns the responses of previous
+    /// runs which the current run didn't replace, so they can be deleted.
+    pub(crate) fn finish_command(
+        &mut self,
+        user_msg_id: serenity::MessageId,
+    ) -> Vec<TrackedResponse> {
+        let leftover_responses = match self.cache.get_mut(&user_msg_id) {
+            Some(invocation) => {
+                invocation.running = false;
+                invocation
+                    .bot_responses
+                    .split_off(invocation.responses_in_current_run)
+            }
+            None => Vec::new(),
+        };
+        for response in &leftover_responses {
+            self.response_owners.remove(&response.id);
+        }
+        leftover_responses
+    }
+
+    /// Returns the position of the next response of the current command run, and the response
+    /// at that position from a previous run, if any
+    fn next_response_slot(
         &mut self,
         user_msg_id: serenity::MessageId,
-    ) -> Option<&mut serenity::Message> {
-        let (_, bot_response) = self
-            .cache
-            .iter_mut()
-            .find(|(user_msg, _)| user_msg.id == user_msg_id)?;
-        Some(bot_response)
+    ) -> (usize, Option<TrackedResponse>) {
+        match self.cache.get_mut(&user_msg_id) {
+            Some(invocation) => {
+                let index = invocation.responses_in_current_run;
+                invocation.responses_in_current_run += 1;
+                (index, invocation.bot_responses.get(index).cloned())
+            }
+            None => (0, None),
+        }
+    }
+
+    /// Registers the bot response at the given position for a user message, replacing the
+    /// previous response at that position if any. Starts tracking the user message if it isn't
+    /// already.
+    ///
+    /// Use this to make responses sent outside of [`send_prefix_reply`] participate in edit
+    /// tracking. The position must be at most the number of existing responses; larger positions
+    /// append the response. Every response message must only be registered for one user message.
+    /// The replaced response, if any, is forgotten but not deleted.
+    pub fn register_response(
+        &mut self,
+        user_msg: &serenity::Message,
+        index: usize,
+        bot_response: &serenity::Message,
+    ) {
+        self.register_tracked_response(user_msg, index, TrackedResponse::new(bot_response));
+    }
+
+    /// Like [`Self::register_response`], but with the attachment hashes already filled in
+    fn register_tracked_response(
+        &mut self,
+        user_msg: &serenity::Message,
+        index: usize,
+        bot_response: TrackedResponse,
+    ) {
+        let bot_response_id = bot_response.id;
+        let replaced_response = match self.touch(user_msg.id) {
+            Some(invocation) => match invocation.bot_responses.get_mut(index) {
+                Some(existing_response) => Some(std::mem::replace(existing_response, bot_response)),
+                None => {
+                    invocation.bot_responses.push(bot_response);
+                    None
+                }
+            },
+            None => {
+                self.insert(user_msg, vec![bot_response]);
+                None
+            }
+        };
+        if let Some(replaced_response) = replaced_response {
+            self.response_owners.remove(&replaced_response.id);
+        }
+        self.response_owners.insert(bot_response_id, user_msg.id);
+    }
+
+    /// Refreshes the stored state of a tracked bot response, like its attachments, after it was
+    /// edited outside of a command re-run. Does nothing if the response isn't tracked.
+    pub fn update_response(&mut self, bot_response: &serenity::Message) {
+        let user_msg_id = match self.response_owners.get(&bot_response.id) {
+            Some(&x) => x,
+            None => return,
+        };
+        if let Some(invocation) = self.cache.get_mut(&user_msg_id) {
+            if let Some(existing_response) = invocation
+                .bot_responses
+                .iter_mut()
+                .find(|response| response.id == bot_response.id)
+            {
+                *existing_response = TrackedResponse::new(bot_response);
+            }
+        }
+    }
+}
+
+/// Replaces the content, embeds and components of an existing response. Parts that the new reply
+/// doesn't have are removed, e.g. buttons of the previous run of an edit tracked command.
+pub(crate) fn fill_response_edit(
+    f: &mut serenity::EditMessage<'_>,
+    content: Option<String>,
+    embeds: Vec<serenity::CreateEmbed>,
+    components: Option<serenity::CreateComponents>,
+    allowed_mentions: Option<serenity::CreateAllowedMentions>,
+    suppress_embeds: bool,
+) {
+    // Empty string resets content (happens when user replaces text with embed)
+    f.content(content.unwrap_or_default());
+    // Message edits can only toggle this one flag
+    let flags = if suppress_embeds {
+        crate::SUPPRESS_EMBEDS_FLAG
+    } else {
+        0
+    };
+    f.0.insert("flags", serde_json::Value::from(flags));
+    crate::replace_embeds(&mut f.0, embeds);
+    f.components(|f| {
+        *f = components.unwrap_or_default();
+        f
+    });
+    if let Some(allowed_mentions) = allowed_mentions {
+        f.allowed_mentions(|f| {
+            *f = allowed_mentions;
+            f
+        });
     }
+}
 
-    /// Returns a reference to the bot response that was just registered
-    fn register_response(&mut self, user_msg: serenity::Message, bot_response: serenity::Message) {
-        self.cache.push((user_msg, bot_response));
+/// Deletes the response and, if requested, the invocation message after the given delay, to
+/// emulate ephemeral replies
+fn spawn_delayed_deletion<U, E>(
+    ctx: crate::PrefixContext<'_, U, E>,
+    response: &serenity::Message,
+    after: std::time::Duration,
+    delete_invocation: bool,
+) {
+    let http = ctx.discord.http.clone();
+    let channel_id = response.channel_id;
+    let mut message_ids = vec![response.id];
+    if delete_invocation {
+        message_ids.push(ctx.msg.id);
     }
+
+    tokio::spawn(async move {
+        tokio::time::sleep(after).await;
+        for message_id in message_ids {
+            // The message may have been deleted in the meantime, e.g. by the user
+            if let Err(e) = channel_id.delete_message(&http, message_id).await {
+                if !is_unknown_message_error(&e) {
+                    println!("Failed to delete ephemeral prefix reply: {}", e);
+                }
+            }
+        }
+    });
 }
 
-pub async fn send_prefix_reply<U, E>(
-    ctx: crate::prefix::PrefixContext<'_, U, E>,
+/// Hashes the file name and contents of an attachment to be sent, so that a re-run of the command
+/// can tell whether the attachment changed. Returns None if the contents can't be read, e.g. for
+/// attachments from URLs.
+async fn hash_attachment(attachment: &serenity::AttachmentType<'_>) -> Option<u64> {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    match attachment {
+        serenity::AttachmentType::Bytes { data, filename } => {
+            filename.hash(&mut hasher);
+            data.hash(&mut hasher);
+        }
+        serenity::AttachmentType::Path(path) => {
+            path.file_name()?.hash(&mut hasher);
+            tokio::fs::read(path).await.ok()?.hash(&mut hasher);
+        }
+        _ => return None,
+    }
+    Some(hasher.finish())
+}
+
+/// Whether the attachments to be sent are the same as the already uploaded ones, given by their
+/// hashes. Attachments whose contents are unknown are never considered the same.
+fn attachments_unchanged(new: &[Option<u64>], existing: &[Option<u64>]) -> bool {
+    new.iter().all(Option::is_some) && new == existing
+}
+
+/// Whether the error is Discord's "Unknown Message" error, i.e. the message doesn't exist
+fn is_unknown_message_error(error: &serenity::Error) -> bool {
+    const UNKNOWN_MESSAGE: isize = 10008;
+
+    match error {
+        serenity::Error::Http(http_error) => match &**http_error {
+            serenity::HttpError::UnsuccessfulRequest(response) => {
+                response.error.code == UNKNOWN_MESSAGE
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+pub async fn send_prefix_reply<'ctx, U, E>(
+    ctx: crate::prefix::PrefixContext<'ctx, U, E>,
     builder: impl for<'a, 'b> FnOnce(&'a mut crate::CreateReply<'b>) -> &'a mut crate::CreateReply<'b>,
-) -> Result<(), serenity::Error> {
+) -> Result<crate::ReplyHandle<'ctx, U, E>, serenity::Error> {
+    // Typing would otherwise resume after the response was sent
+    ctx.framework.stop_typing(ctx.msg.id);
+
     let mut reply = crate::CreateReply::default();
     builder(&mut reply);
+    if let Some(reply_callback) = ctx.framework.options().reply_callback {
+        reply_callback(crate::Context::Prefix(ctx), &mut reply);
+    }
+    reply.apply_sanitization();
+    let ephemeral = reply.ephemeral;
+    let allowed_mentions = reply.allowed_mentions.clone();
+    let suppress_embeds = reply.suppress_embeds;
+    let additional_chunks = reply.split_off_content();
+
+    let handle = send_prefix_reply_inner(ctx, reply).await?;
+    for chunk in additional_chunks {
+        let reply = crate::CreateReply {
+            content: Some(chunk),
+            allowed_mentions: allowed_mentions.clone(),
+            // Only the first message refers to the invocation
+            reply: Some(crate::ReplyBehavior::NoReply),
+            suppress_embeds,
+            ephemeral,
+            ..Default::default()
+        };
+        send_prefix_reply_inner(ctx, reply).await?;
+    }
+    Ok(handle)
+}
+
+async fn send_prefix_reply_inner<'ctx, U, E>(
+    ctx: crate::prefix::PrefixContext<'ctx, U, E>,
+    reply: crate::CreateReply<'_>,
+) -> Result<crate::ReplyHandle<'ctx, U, E>, serenity::Error> {
     let crate::CreateReply {
         content,
-        embed,
+        embeds,
         attachments,
-        ephemeral: _,
+        components,
+        allowed_mentions,
+        reply,
+        reactions,
+        split: _,
+        sanitize_content: _,
+        suppress_embeds,
+        ephemeral,
+        keep_existing_attachments,
+        reuse_response,
     } = reply;
+    let allowed_mentions =
+        allowed_mentions.or_else(|| ctx.framework.options().allowed_mentions.clone());
+    let reply = reply
+        .or_else(|| {
+            ctx.command
+                .and_then(|command| command.options.reply_to_invocation)
+        })
+        .unwrap_or(ctx.framework.options().prefix_options.reply_to_invocation);
+    let allowed_mentions = match reply {
+        crate::ReplyBehavior::ReplyWithoutPing => {
+            let mut allowed_mentions = allowed_mentions.unwrap_or_else(|| {
+                // Without allowed mentions, Discord parses all mentions, so keep doing that
+                let mut allowed_mentions = serenity::CreateAllowedMentions::default();
+                allowed_mentions
+                    .parse(serenity::ParseValue::Everyone)
+                    .parse(serenity::ParseValue::Users)
+                    .parse(serenity::ParseValue::Roles);
+                allowed_mentions
+            });
+            allowed_mentions.replied_user(false);
+            Some(allowed_mentions)
+        }
+        crate::ReplyBehavior::NoReply | crate::ReplyBehavior::Reply => allowed_mentions,
+    };
 
     let lock_edit_tracker = || {
         if let Some(command) = ctx.command {
@@ -139,72 +746,520 @@ pub async fn send_prefix_reply<U, E>(
             .map(|t| t.write())
     };
 
-    let existing_response = lock_edit_tracker()
-        .as_mut()
-        .and_then(|t| t.find_bot_response(ctx.msg.id))
-        .cloned();
-
-    if let Some(mut response) = existing_response {
-        response
-            .edit(ctx.discord, |f| {
-                // Empty string resets content (happens when user replaces text with embed)
-                f.content(content.as_deref().unwrap_or(""));
-
-                match embed {
-                    Some(embed) => f.set_embed(embed),
-                    None => f.set_embeds(Vec::new()),
-                };
-
-                f.0.insert("attachments", serde_json::json! { [] }); // reset attachments
-                for attachment in attachments {
-                    f.attachment(attachment);
+    let (is_tracked, response_index, existing_response) = match &mut lock_edit_tracker() {
+        Some(t) => {
+            let (response_index, existing_response) = t.next_response_slot(ctx.msg.id);
+            (true, response_index, existing_response)
+        }
+        None => (false, 0, None),
+    };
+    let reuse_response = reuse_response
+        .or_else(|| ctx.command.map(|command| command.options.reuse_response))
+        .unwrap_or(true);
+    let existing_response = existing_response.filter(|_| reuse_response);
+
+    // Only needed to compare the attachments of future re-runs of the command
+    let mut attachment_hashes = Vec::new();
+    if is_tracked {
+        for attachment in &attachments {
+            attachment_hashes.push(hash_attachment(attachment).await);
+        }
+    }
+
+    let mut response = None;
+    if let Some(existing_response) = existing_response {
+        // Avoid needless re-uploads if the attachments are the same as before
+        let attachments_unchanged = !keep_existing_attachments
+            && attachments_unchanged(&attachment_hashes, &existing_response.attachment_hashes);
+
+        let edit_result = existing_response
+            .channel_id
+            .edit_message(ctx.discord, existing_response.id, |f| {
+                fill_response_edit(
+                    f,
+                    content.clone(),
+                    embeds.clone(),
+                    components.clone(),
+                    allowed_mentions.clone(),
+                    suppress_embeds,
+                );
+
+                if !attachments_unchanged {
+                    if !keep_existing_attachments {
+                        f.0.insert("attachments", serde_json::json! { [] }); // reset attachments
+                    }
+                    for attachment in &attachments {
+                        f.attachment(attachment.clone());
+                    }
                 }
 
                 f
             })
-            .await?;
+            .await;
+        match edit_result {
+            Ok(edited_response) => {
+                // The new attachments were added after the existing ones
+                if keep_existing_attachments {
+                    attachment_hashes.splice(0..0, existing_response.attachment_hashes);
+                }
+                response = Some(edited_response);
+            }
+            // The response was deleted in the meantime; send a new one instead
+            Err(e) if is_unknown_message_error(&e) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let is_new_response = response.is_none();
+    let response = match response {
+        Some(response) => response,
+        None => {
+            ctx.msg
+                .channel_id
+                .send_message(ctx.discord, |m| {
+                    if let Some(content) = content {
+                        m.content(content);
+                    }
+                    crate::replace_embeds(&mut m.0, embeds);
+                    if reply != crate::ReplyBehavior::NoReply {
+                        m.reference_message(ctx.msg);
+                    }
+                    if suppress_embeds {
+                        crate::add_suppress_embeds_flag(&mut m.0);
+                    }
+                    if let Some(components) = components {
+                        m.components(|m| {
+                            *m = components;
+                            m
+                        });
+                    }
+                    if let Some(allowed_mentions) = allowed_mentions {
+                        m.allowed_mentions(|m| {
+                            *m = allowed_mentions;
+                            m
+                        });
+                    }
 
-        // If the entry still exists after the await, update it to the new contents
-        if let Some(response_entry) = lock_edit_tracker()
-            .as_mut()
-            .and_then(|t| t.find_bot_response(ctx.msg.id))
-        {
-            *response_entry = response;
+                    for attachment in attachments {
+                        m.add_file(attachment);
+                    }
+                    m
+                })
+                .await?
         }
-    } else {
-        let new_response = ctx
-            .msg
-            .channel_id
-            .send_message(ctx.discord, |m| {
-                if let Some(content) = content {
-                    m.content(content);
-                }
-                if let Some(embed) = embed {
-                    m.set_embed(embed);
-                }
-                if let Some(allowed_mentions) = &ctx.framework.options().allowed_mentions {
-                    m.allowed_mentions(|m| {
-                        *m = allowed_mentions.clone();
-                        m
-                    });
-                }
+    };
 
-                for attachment in attachments {
-                    m.add_file(attachment);
-                }
-                m
-            })
-            .await?;
-        if let Some(track_edits) = &mut lock_edit_tracker() {
-            track_edits.register_response(ctx.msg.clone(), new_response);
+    // Update the entry to the new contents
+    if let Some(track_edits) = &mut lock_edit_tracker() {
+        let tracked_response = TrackedResponse {
+            attachment_hashes,
+            ..TrackedResponse::new(&response)
+        };
+        track_edits.register_tracked_response(ctx.msg, response_index, tracked_response);
+    }
+    for reaction in reactions {
+        response.react(ctx.discord, reaction).await?;
+    }
+
+    if let crate::PrefixEphemeralBehavior::Delete {
+        after,
+        delete_invocation,
+    } = ctx.framework.options().prefix_options.ephemeral_behavior
+    {
+        if ephemeral && is_new_response {
+            spawn_delayed_deletion(ctx, &response, after, delete_invocation);
         }
     }
-    Ok(())
+
+    Ok(crate::ReplyHandle::prefix(ctx, response))
 }
 
-pub async fn say_prefix_reply<U, E>(
-    ctx: crate::prefix::PrefixContext<'_, U, E>,
-    text: String,
-) -> Result<(), serenity::Error> {
-    send_prefix_reply(ctx, |m| m.content(text)).await
+pub async fn say_prefix_reply<'ctx, U, E>(
+    ctx: crate::prefix::PrefixContext<'ctx, U, E>,
+    text: impl Into<String>,
+) -> Result<crate::ReplyHandle<'ctx, U, E>, serenity::Error> {
+    send_prefix_reply(ctx, |m| m.content(text.into())).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn message(id: u64) -> serenity::Message {
+        serenity::CustomMessage::new()
+            .id(serenity::MessageId(id))
+            .build()
+    }
+
+    #[test]
+    fn test_zero_purge_interval_is_clamped() {
+        let tracker = EditTracker::for_timespan(std::time::Duration::ZERO);
+        assert_eq!(tracker.read().purge_interval(), MIN_PURGE_INTERVAL);
+    }
+
+    #[test]
+    fn test_max_entries_evicts_least_recently_updated() {
+        let tracker = EditTracker::for_timespan_with_limit(std::time::Duration::from_secs(60), 2);
+        let mut tracker = tracker.write();
+
+        tracker.register_response(&message(1), 0, &message(101));
+        tracker.register_response(&message(2), 0, &message(102));
+        // Refresh 1, so that 2 becomes the least recently updated entry
+        tracker.register_response(&message(1), 0, &message(111));
+        tracker.track_command(&message(3));
+
+        assert_eq!(
+            tracker
+                .find_bot_response(serenity::MessageId(1))
+                .map(|m| m.id()),
+            Some(serenity::MessageId(111))
+        );
+        assert!(tracker.find_bot_response(serenity::MessageId(2)).is_none());
+        assert_eq!(tracker.cache.len(), 2);
+    }
+
+    #[test]
+    fn test_multiple_responses_per_invocation() {
+        let tracker = EditTracker::for_timespan(std::time::Duration::from_secs(60));
+        let mut tracker = tracker.write();
+        let user_msg = message(1);
+
+        // First run sends three responses
+        tracker.track_command(&user_msg);
+        for response_id in 101..=103 {
+            let (index, existing) = tracker.next_response_slot(user_msg.id);
+            assert!(existing.is_none());
+            tracker.register_response(&user_msg, index, &message(response_id));
+        }
+        assert!(tracker.finish_command(user_msg.id).is_empty());
+
+        // Second run only sends two responses, which replace the first two
+        tracker.track_command(&user_msg);
+        for expected_existing in 101..=102 {
+            let (index, existing) = tracker.next_response_slot(user_msg.id);
+            assert_eq!(
+                existing.map(|m| m.id()),
+                Some(serenity::MessageId(expected_existing))
+            );
+            tracker.register_response(&user_msg, index, &message(expected_existing));
+        }
+        let leftovers = tracker.finish_command(user_msg.id);
+        assert_eq!(
+            leftovers.iter().map(|m| m.id()).collect::<Vec<_>>(),
+            vec![serenity::MessageId(103)]
+        );
+        assert_eq!(
+            tracker.remove(user_msg.id).unwrap().bot_responses().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_remove_bot_response() {
+        let tracker = EditTracker::for_timespan(std::time::Duration::from_secs(60));
+        let mut tracker = tracker.write();
+        let user_msg = message(1);
+
+        tracker.track_command(&user_msg);
+        for response_id in 101..=102 {
+            let (index, _) = tracker.next_response_slot(user_msg.id);
+            tracker.register_response(&user_msg, index, &message(response_id));
+        }
+        tracker.finish_command(user_msg.id);
+
+        assert_eq!(
+            tracker.remove_bot_response(serenity::MessageId(101)),
+            Some(user_msg.id)
+        );
+        assert_eq!(tracker.remove_bot_response(serenity::MessageId(101)), None);
+        assert_eq!(
+            tracker.find_bot_response(user_msg.id).map(|m| m.id()),
+            Some(serenity::MessageId(102))
+        );
+    }
+
+    #[test]
+    fn test_process_message_update() {
+        let tracker = EditTracker::for_timespan(std::time::Duration::from_secs(60));
+        let mut tracker = tracker.write();
+        let user_msg = serenity::CustomMessage::new()
+            .id(serenity::MessageId(1))
+            .content("~plot x")
+            .build();
+        tracker.track_command(&user_msg);
+        tracker.register_response(&user_msg, 0, &message(101));
+
+        let update: serenity::MessageUpdateEvent = serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "channel_id": "2",
+            "content": "~plot x^2",
+            "embeds": [{ "type": "rich", "title": "Preview", "color": 0, "fields": [] }],
+            "attachments": [{
+                "id": "3",
+                "filename": "plot.png",
+                "size": 1024,
+                "url": "https://cdn.discordapp.com/attachments/2/3/plot.png",
+                "proxy_url": "https://media.discordapp.net/attachments/2/3/plot.png",
+            }],
+        }))
+        .unwrap();
+
+        let (updated_msg, previously_tracked) = tracker
+            .process_message_update(&update, false, false)
+            .unwrap();
+        assert!(previously_tracked);
+        assert_eq!(updated_msg.content, "~plot x^2");
+        assert_eq!(updated_msg.embeds.len(), 1);
+        assert_eq!(updated_msg.embeds[0].title.as_deref(), Some("Preview"));
+        assert_eq!(updated_msg.attachments.len(), 1);
+        assert_eq!(updated_msg.attachments[0].filename, "plot.png");
+
+        // Same content again, e.g. because an embed resolved: no re-run
+        assert!(tracker
+            .process_message_update(&update, false, false)
+            .is_none());
+    }
+
+    #[test]
+    fn test_ignore_edits_if_not_yet_responded() {
+        let tracker = EditTracker::for_timespan(std::time::Duration::from_secs(60));
+        let mut tracker = tracker.write();
+        let user_msg = serenity::CustomMessage::new()
+            .id(serenity::MessageId(1))
+            .content("~ping")
+            .build();
+        let update = |content: &str| -> serenity::MessageUpdateEvent {
+            serde_json::from_value(serde_json::json!({
+                "id": "1",
+                "channel_id": "2",
+                "content": content,
+                "author": serenity::User::default(),
+            }))
+            .unwrap()
+        };
+
+        // Still running without a response
+        tracker.track_command(&user_msg);
+        assert!(tracker
+            .process_message_update(&update("~pong"), true, false)
+            .is_none());
+
+        // Finished without ever responding
+        tracker.finish_command(user_msg.id);
+        assert!(tracker
+            .process_message_update(&update("~ping"), true, false)
+            .is_some());
+
+        // A re-run which responded, then a re-run which didn't
+        tracker.track_command(&user_msg);
+        let (index, _) = tracker.next_response_slot(user_msg.id);
+        tracker.register_response(&user_msg, index, &message(101));
+        tracker.finish_command(user_msg.id);
+        tracker.track_command(&user_msg);
+        tracker.finish_command(user_msg.id);
+        assert!(tracker.get(user_msg.id).unwrap().bot_responses().is_empty());
+        assert!(tracker
+            .process_message_update(&update("~pong"), true, false)
+            .is_some());
+    }
+
+    #[test]
+    fn test_process_message_update_without_content() {
+        let tracker = EditTracker::for_timespan(std::time::Duration::from_secs(60));
+        let mut tracker = tracker.write();
+        let mut author = serenity::User::default();
+        author.id = serenity::UserId(5);
+        let user_msg = serenity::CustomMessage::new()
+            .id(serenity::MessageId(1))
+            .channel_id(serenity::ChannelId(2))
+            .author(author)
+            .content("~plot x")
+            .build();
+        tracker.track_command(&user_msg);
+        tracker.register_response(&user_msg, 0, &message(101));
+
+        // A resolved link embed: the update contains neither content nor author
+        let update: serenity::MessageUpdateEvent = serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "channel_id": "2",
+            "embeds": [{ "type": "link", "url": "https://example.com", "fields": [] }],
+        }))
+        .unwrap();
+
+        assert!(tracker
+            .process_message_update(&update, false, false)
+            .is_none());
+        let (updated_msg, previously_tracked) = tracker
+            .process_message_update(&update, false, true)
+            .unwrap();
+        assert!(previously_tracked);
+        assert_eq!(updated_msg.content, "~plot x");
+        assert_eq!(updated_msg.author.id, serenity::UserId(5));
+        assert_eq!(updated_msg.embeds.len(), 1);
+
+        // Untracked messages can't be rebuilt from such an update
+        let update: serenity::MessageUpdateEvent = serde_json::from_value(serde_json::json!({
+            "id": "3",
+            "channel_id": "2",
+            "embeds": [],
+        }))
+        .unwrap();
+        assert!(tracker
+            .process_message_update(&update, false, true)
+            .is_none());
+    }
+
+    #[test]
+    fn test_response_edit_removes_components() {
+        let mut first_run = serenity::EditMessage::default();
+        let mut components = serenity::CreateComponents::default();
+        components.create_action_row(|f| f.create_button(|f| f.custom_id("vote:1").label("Vote")));
+        fill_response_edit(
+            &mut first_run,
+            Some("Vote now".into()),
+            Vec::new(),
+            Some(components),
+            None,
+            false,
+        );
+        assert_eq!(first_run.0["components"].as_array().map(Vec::len), Some(1));
+
+        // The re-run after the user edited their message doesn't send buttons anymore
+        let mut second_run = serenity::EditMessage::default();
+        fill_response_edit(
+            &mut second_run,
+            Some("Voting closed".into()),
+            Vec::new(),
+            None,
+            None,
+            false,
+        );
+        assert_eq!(second_run.0["components"], serde_json::json!([]));
+        assert_eq!(second_run.0["content"], serde_json::json!("Voting closed"));
+    }
+
+    #[test]
+    fn test_response_edit_allowed_mentions() {
+        let mut without = serenity::EditMessage::default();
+        fill_response_edit(
+            &mut without,
+            Some("Hi".into()),
+            Vec::new(),
+            None,
+            None,
+            false,
+        );
+        assert!(!without.0.contains_key("allowed_mentions"));
+
+        let mut with = serenity::EditMessage::default();
+        fill_response_edit(
+            &mut with,
+            Some("Hi".into()),
+            Vec::new(),
+            None,
+            Some(serenity::CreateAllowedMentions::default()),
+            false,
+        );
+        assert!(with.0.contains_key("allowed_mentions"));
+    }
+
+    #[test]
+    fn test_response_edit_suppress_embeds_flag() {
+        let mut first_run = serenity::EditMessage::default();
+        fill_response_edit(
+            &mut first_run,
+            Some("https://example.com".into()),
+            Vec::new(),
+            None,
+            None,
+            true,
+        );
+        assert_eq!(
+            first_run.0["flags"],
+            serde_json::json!(crate::SUPPRESS_EMBEDS_FLAG)
+        );
+
+        // The re-run doesn't suppress embeds anymore, so the flag must be cleared
+        let mut second_run = serenity::EditMessage::default();
+        fill_response_edit(
+            &mut second_run,
+            Some("https://example.com".into()),
+            Vec::new(),
+            None,
+            None,
+            false,
+        );
+        assert_eq!(second_run.0["flags"], serde_json::json!(0));
+    }
+
+    #[test]
+    fn test_response_edit_replaces_embeds() {
+        let mut embed = serenity::CreateEmbed::default();
+        embed.title("Result");
+
+        let mut first_run = serenity::EditMessage::default();
+        fill_response_edit(
+            &mut first_run,
+            None,
+            vec![embed.clone(), embed],
+            None,
+            None,
+            false,
+        );
+        assert_eq!(first_run.0["embeds"].as_array().map(Vec::len), Some(2));
+        assert_eq!(
+            first_run.0["embeds"][0]["title"],
+            serde_json::json!("Result")
+        );
+
+        // The re-run doesn't reply with embeds anymore, so the previous ones must be removed
+        let mut second_run = serenity::EditMessage::default();
+        fill_response_edit(
+            &mut second_run,
+            Some("No results".into()),
+            Vec::new(),
+            None,
+            None,
+            false,
+        );
+        assert_eq!(second_run.0["embeds"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_rerun_with_changed_attachment() {
+        async fn hashes(reply: &crate::CreateReply<'_>) -> Vec<Option<u64>> {
+            let mut hashes = Vec::new();
+            for attachment in &reply.attachments {
+                hashes.push(hash_attachment(attachment).await);
+            }
+            hashes
+        }
+
+        // The response to the first run, as registered in the edit tracker
+        let mut first_run = crate::CreateReply::default();
+        first_run.attachment_bytes("report.txt", b"hello".to_vec());
+        let existing = hashes(&first_run).await;
+
+        let mut unchanged = crate::CreateReply::default();
+        unchanged.attachment_bytes("report.txt", b"hello".to_vec());
+        assert!(attachments_unchanged(&hashes(&unchanged).await, &existing));
+
+        // Either a different file name or different contents make the edit re-upload the file
+        let mut longer = crate::CreateReply::default();
+        longer.attachment_bytes("report.txt", b"hello world".to_vec());
+        assert!(!attachments_unchanged(&hashes(&longer).await, &existing));
+        // Same size, different contents
+        let mut same_size = crate::CreateReply::default();
+        same_size.attachment_bytes("report.txt", b"world".to_vec());
+        assert!(!attachments_unchanged(&hashes(&same_size).await, &existing));
+        let mut renamed = crate::CreateReply::default();
+        renamed.attachment_bytes("summary.txt", b"hello".to_vec());
+        assert!(!attachments_unchanged(&hashes(&renamed).await, &existing));
+
+        // Attachments with unknown contents are always re-uploaded
+        let mut from_path = crate::CreateReply::default();
+        from_path.attachment_path("does/not/exist/report.txt");
+        assert_eq!(hashes(&from_path).await, [None]);
+        assert!(!attachments_unchanged(&[None], &[None]));
+    }
 }
@@ -3,9 +3,30 @@ use crate::serenity_prelude as serenity;
 #[derive(Default)]
 pub struct CreateReply<'a> {
     pub content: Option<String>,
-    pub embed: Option<serenity::CreateEmbed>,
+    /// Embeds of the message. When editing an existing response, these replace all of its
+    /// previous embeds, so no embeds removes them.
+    pub embeds: Vec<serenity::CreateEmbed>,
     pub attachments: Vec<serenity::AttachmentType<'a>>,
+    /// Message components like buttons and select menus. When editing an existing response, no
+    /// components removes the previous ones.
+    pub components: Option<serenity::CreateComponents>,
+    /// Overrides [`crate::FrameworkOptions::allowed_mentions`] for this reply
+    pub allowed_mentions: Option<serenity::CreateAllowedMentions>,
+    /// See [`Self::reply`]. Falls back to the command's or framework's default on None.
+    pub reply: Option<crate::ReplyBehavior>,
+    /// Reactions added to the message after sending
+    pub reactions: Vec<serenity::ReactionType>,
+    /// See [`Self::split`]
+    pub split: bool,
+    /// See [`Self::sanitize_content`]
+    pub sanitize_content: bool,
+    /// See [`Self::suppress_embeds`]
+    pub suppress_embeds: bool,
     pub ephemeral: bool,
+    /// See [`Self::keep_existing_attachments`]
+    pub keep_existing_attachments: bool,
+    /// See [`Self::reuse_response`]
+    pub reuse_response: Option<bool>,
 }
 
 impl<'a> CreateReply<'a> {
@@ -15,49 +36,610 @@ impl<'a> CreateReply<'a> {
         self
     }
 
-    /// Set an embed for the message.
-    ///
-    /// Any previously set embed will be overwritten.
+    /// Add an embed to the message. Discord allows up to ten embeds per message.
     pub fn embed(
         &mut self,
         f: impl FnOnce(&mut serenity::CreateEmbed) -> &mut serenity::CreateEmbed,
     ) -> &mut Self {
         let mut embed = serenity::CreateEmbed::default();
         f(&mut embed);
-        self.embed = Some(embed);
+        self.embeds.push(embed);
+        self
+    }
+
+    /// Set the embeds of the message, replacing any previously added embeds.
+    pub fn set_embeds(&mut self, embeds: Vec<serenity::CreateEmbed>) -> &mut Self {
+        self.embeds = embeds;
         self
     }
 
     /// Add an attachment.
     ///
-    /// This will not have an effect in a slash command's initial response!
+    /// In slash commands, the initial response can't carry files, so replies with attachments
+    /// are sent as a follow-up to a deferred response instead.
     pub fn attachment(&mut self, attachment: serenity::AttachmentType<'a>) -> &mut Self {
         self.attachments.push(attachment);
         self
     }
 
+    /// Add an attachment with the given file name and contents.
+    pub fn attachment_bytes(&mut self, filename: impl Into<String>, data: Vec<u8>) -> &mut Self {
+        self.attachment(serenity::AttachmentType::Bytes {
+            data: std::borrow::Cow::Owned(data),
+            filename: filename.into(),
+        })
+    }
+
+    /// Add an attachment from a file on disk. The file is read when the message is sent.
+    pub fn attachment_path(
+        &mut self,
+        path: &'a (impl AsRef<std::path::Path> + ?Sized),
+    ) -> &mut Self {
+        self.attachment(serenity::AttachmentType::Path(path.as_ref()))
+    }
+
+    /// Set the message components, like buttons and select menus.
+    ///
+    /// Interactions with the components can be handled via
+    /// [`crate::FrameworkOptions::component_handlers`] or collectors.
+    pub fn components(
+        &mut self,
+        f: impl FnOnce(&mut serenity::CreateComponents) -> &mut serenity::CreateComponents,
+    ) -> &mut Self {
+        let mut components = serenity::CreateComponents::default();
+        f(&mut components);
+        self.components = Some(components);
+        self
+    }
+
+    /// Set the allowed mentions of this reply. Replaces
+    /// [`crate::FrameworkOptions::allowed_mentions`] for this reply only.
+    pub fn allowed_mentions(
+        &mut self,
+        f: impl FnOnce(&mut serenity::CreateAllowedMentions) -> &mut serenity::CreateAllowedMentions,
+    ) -> &mut Self {
+        let mut allowed_mentions = serenity::CreateAllowedMentions::default();
+        f(&mut allowed_mentions);
+        self.allowed_mentions = Some(allowed_mentions);
+        self
+    }
+
+    /// Whether to send the message as a Discord reply to the invoking message, so it's clear which
+    /// invocation it belongs to.
+    ///
+    /// Only has an effect in prefix commands; slash command responses are always linked to the
+    /// invocation. Existing responses, e.g. of edit tracked commands, can't be turned into replies
+    /// when edited. Whether the reply pings the author is controlled by
+    /// [`serenity::CreateAllowedMentions::replied_user`], which defaults to no ping when allowed
+    /// mentions are set. Use [`Self::reply_behavior`] to reply without ping regardless.
+    ///
+    /// Overrides [`crate::PrefixCommandOptions::reply_to_invocation`] and
+    /// [`crate::PrefixFrameworkOptions::reply_to_invocation`].
+    pub fn reply(&mut self, reply: bool) -> &mut Self {
+        self.reply = Some(if reply {
+            crate::ReplyBehavior::Reply
+        } else {
+            crate::ReplyBehavior::NoReply
+        });
+        self
+    }
+
+    /// Like [`Self::reply`], but also allows replying without pinging the invoking user.
+    pub fn reply_behavior(&mut self, reply_behavior: crate::ReplyBehavior) -> &mut Self {
+        self.reply = Some(reply_behavior);
+        self
+    }
+
+    /// Add a reaction to the message after sending it. Works with unicode emojis like `'✅'` and
+    /// custom emojis.
+    ///
+    /// Ephemeral messages can't be reacted to.
+    pub fn reaction(&mut self, reaction: impl Into<serenity::ReactionType>) -> &mut Self {
+        self.reactions.push(reaction.into());
+        self
+    }
+
+    /// Whether to split content longer than Discord's limit of 2000 characters into multiple
+    /// messages. Content is split at line boundaries where possible, and code blocks are closed
+    /// and reopened with the same language around each split.
+    ///
+    /// The first message carries the rest of the reply, like embeds and attachments. The returned
+    /// handle refers to the first message. In edit tracked commands, each message takes its own
+    /// response slot, so re-runs edit them in order.
+    pub fn split(&mut self, split: bool) -> &mut Self {
+        self.split = split;
+        self
+    }
+
+    /// Whether to neutralize mentions in the content before sending, using
+    /// [`sanitize_mentions`]. Useful when echoing user input.
+    ///
+    /// This works on top of [`Self::allowed_mentions`]: the content stays non-pinging even if the
+    /// allowed mentions permit pings. To sanitize all replies, set this flag in
+    /// [`crate::FrameworkOptions::reply_callback`].
+    pub fn sanitize_content(&mut self, sanitize_content: bool) -> &mut Self {
+        self.sanitize_content = sanitize_content;
+        self
+    }
+
+    /// Whether to hide the link previews Discord generates for URLs in the content. Embeds added
+    /// to this reply are unaffected.
+    pub fn suppress_embeds(&mut self, suppress_embeds: bool) -> &mut Self {
+        self.suppress_embeds = suppress_embeds;
+        self
+    }
+
+    /// Applies [`Self::sanitize_content`] to the content, if enabled
+    pub(crate) fn apply_sanitization(&mut self) {
+        if self.sanitize_content {
+            if let Some(content) = &mut self.content {
+                *content = sanitize_mentions(content);
+            }
+        }
+    }
+
+    /// Splits off the content exceeding the message length limit if [`Self::split`] is enabled.
+    /// Returns the content of the additional messages to send after this one.
+    pub(crate) fn split_off_content(&mut self) -> Vec<String> {
+        if !self.split {
+            return Vec::new();
+        }
+        let mut chunks = match &self.content {
+            Some(content) if content.len() > MESSAGE_LENGTH_LIMIT => {
+                split_content(content, MESSAGE_LENGTH_LIMIT)
+            }
+            _ => return Vec::new(),
+        };
+        let remaining_chunks = chunks.split_off(1);
+        self.content = chunks.pop();
+        remaining_chunks
+    }
+
     /// Toggles whether the message is an ephemeral response (only invoking user can see it).
     ///
-    /// This only has an effect in slash commands!
+    /// In prefix commands, this only has an effect if
+    /// [`crate::PrefixFrameworkOptions::ephemeral_behavior`] is set. In slash commands, defaults to
+    /// [`crate::SlashCommandOptions::ephemeral`]. If the response was deferred, the flag given at
+    /// defer time applies to the first reply instead, see [`crate::SlashContext::defer_ephemeral`].
     pub fn ephemeral(&mut self, ephemeral: bool) -> &mut Self {
         self.ephemeral = ephemeral;
         self
     }
+
+    /// When editing an existing response (e.g. in edit tracked prefix commands), keep the
+    /// response's attachments instead of replacing them with this reply's attachments. Any
+    /// attachments added to this reply are appended.
+    ///
+    /// Avoids re-uploading big files whenever the user edits their message.
+    pub fn keep_existing_attachments(&mut self, keep_existing_attachments: bool) -> &mut Self {
+        self.keep_existing_attachments = keep_existing_attachments;
+        self
+    }
+
+    /// Whether to edit the existing response when an edit tracked prefix command is re-run. If
+    /// false, a new message is sent instead and the previous response is left as is.
+    ///
+    /// Falls back to [`crate::PrefixCommandOptions::reuse_response`] if not set.
+    pub fn reuse_response(&mut self, reuse_response: bool) -> &mut Self {
+        self.reuse_response = Some(reuse_response);
+        self
+    }
+}
+
+enum ReplyHandleInner<'ctx, U, E> {
+    /// A response to a prefix command
+    Prefix {
+        ctx: crate::PrefixContext<'ctx, U, E>,
+        message: Box<serenity::Message>,
+    },
+    /// The initial response to an interaction, which is only accessible through the interaction
+    SlashOriginal {
+        ctx: crate::SlashContext<'ctx, U, E>,
+    },
+    /// A follow-up message to an interaction
+    SlashFollowup {
+        ctx: crate::SlashContext<'ctx, U, E>,
+        message: Box<serenity::Message>,
+    },
+}
+
+/// Returned from the reply functions. Refers to the sent message and allows fetching, editing,
+/// or deleting it later.
+pub struct ReplyHandle<'ctx, U, E>(ReplyHandleInner<'ctx, U, E>);
+
+impl<'ctx, U, E> ReplyHandle<'ctx, U, E> {
+    pub(crate) fn prefix(
+        ctx: crate::PrefixContext<'ctx, U, E>,
+        message: serenity::Message,
+    ) -> Self {
+        Self(ReplyHandleInner::Prefix {
+            ctx,
+            message: Box::new(message),
+        })
+    }
+
+    pub(crate) fn slash_original(ctx: crate::SlashContext<'ctx, U, E>) -> Self {
+        Self(ReplyHandleInner::SlashOriginal { ctx })
+    }
+
+    pub(crate) fn slash_followup(
+        ctx: crate::SlashContext<'ctx, U, E>,
+        message: serenity::Message,
+    ) -> Self {
+        Self(ReplyHandleInner::SlashFollowup {
+            ctx,
+            message: Box::new(message),
+        })
+    }
+
+    /// Returns the message. For initial interaction responses, the message isn't known yet and
+    /// is fetched from Discord.
+    pub async fn message(&self) -> Result<serenity::Message, serenity::Error> {
+        match &self.0 {
+            ReplyHandleInner::Prefix { message, .. } => Ok((**message).clone()),
+            ReplyHandleInner::SlashOriginal { ctx } => {
+                ctx.interaction.get_interaction_response(ctx.discord).await
+            }
+            ReplyHandleInner::SlashFollowup { message, .. } => Ok((**message).clone()),
+        }
+    }
+
+    /// Like [`Self::message`], but consumes the handle, so the message doesn't need to be cloned
+    pub async fn into_message(self) -> Result<serenity::Message, serenity::Error> {
+        match self.0 {
+            ReplyHandleInner::Prefix { message, .. } => Ok(*message),
+            ReplyHandleInner::SlashOriginal { ctx } => {
+                ctx.interaction.get_interaction_response(ctx.discord).await
+            }
+            ReplyHandleInner::SlashFollowup { message, .. } => Ok(*message),
+        }
+    }
+
+    /// Edits the message with the content of the given reply. Fields which aren't set are
+    /// cleared.
+    ///
+    /// Edit tracked prefix command responses stay tracked with their new contents. The ephemeral
+    /// flag can't be changed after sending, and attachments can only be changed on prefix command
+    /// responses.
+    pub async fn edit(
+        &mut self,
+        builder: impl for<'a, 'b> FnOnce(&'a mut CreateReply<'b>) -> &'a mut CreateReply<'b>,
+    ) -> Result<(), serenity::Error> {
+        let ctx = match &self.0 {
+            ReplyHandleInner::Prefix { ctx, .. } => crate::Context::Prefix(*ctx),
+            ReplyHandleInner::SlashOriginal { ctx } => crate::Context::Slash(*ctx),
+            ReplyHandleInner::SlashFollowup { ctx, .. } => crate::Context::Slash(*ctx),
+        };
+
+        let mut reply = CreateReply::default();
+        builder(&mut reply);
+        if let Some(reply_callback) = ctx.framework().options().reply_callback {
+            reply_callback(ctx, &mut reply);
+        }
+        reply.apply_sanitization();
+        let CreateReply {
+            content,
+            embeds,
+            attachments,
+            components,
+            allowed_mentions,
+            reply: _,
+            reactions: _,
+            split: _,
+            sanitize_content: _,
+            suppress_embeds,
+            ephemeral: _,
+            keep_existing_attachments,
+            reuse_response: _,
+        } = reply;
+        let allowed_mentions =
+            allowed_mentions.or_else(|| ctx.framework().options().allowed_mentions.clone());
+
+        match &mut self.0 {
+            ReplyHandleInner::Prefix { ctx, message } => {
+                let edited_message = message
+                    .channel_id
+                    .edit_message(ctx.discord, message.id, |f| {
+                        crate::prefix::fill_response_edit(
+                            f,
+                            content,
+                            embeds,
+                            components,
+                            allowed_mentions,
+                            suppress_embeds,
+                        );
+                        if !keep_existing_attachments {
+                            f.0.insert("attachments", serde_json::json! { [] });
+                            // reset attachments
+                        }
+                        for attachment in attachments {
+                            f.attachment(attachment);
+                        }
+                        f
+                    })
+                    .await?;
+
+                if let Some(edit_tracker) = &ctx.framework.options().prefix_options.edit_tracker {
+                    edit_tracker.write().update_response(&edited_message);
+                }
+                **message = edited_message;
+            }
+            ReplyHandleInner::SlashOriginal { ctx } => {
+                ctx.interaction
+                    .edit_original_interaction_response(ctx.discord, |f| {
+                        f.content(content.as_deref().unwrap_or(""));
+                        crate::replace_embeds(&mut f.0, embeds);
+                        f.components(|f| {
+                            *f = components.unwrap_or_default();
+                            f
+                        });
+                        if let Some(allowed_mentions) = allowed_mentions {
+                            f.allowed_mentions(|f| {
+                                *f = allowed_mentions;
+                                f
+                            });
+                        }
+                        if suppress_embeds {
+                            crate::add_suppress_embeds_flag(&mut f.0);
+                        }
+                        f
+                    })
+                    .await?;
+            }
+            ReplyHandleInner::SlashFollowup { ctx, message } => {
+                **message = ctx
+                    .interaction
+                    .edit_followup_message(ctx.discord, message.id, |f| {
+                        f.content(content.as_deref().unwrap_or(""));
+                        crate::replace_embeds(&mut f.0, embeds);
+                        f.components(|f| {
+                            *f = components.unwrap_or_default();
+                            f
+                        });
+                        if let Some(allowed_mentions) = allowed_mentions {
+                            f.allowed_mentions(|f| {
+                                *f = allowed_mentions;
+                                f
+                            });
+                        }
+                        if suppress_embeds {
+                            crate::add_suppress_embeds_flag(&mut f.0);
+                        }
+                        f
+                    })
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the message. Edit tracked prefix command responses are forgotten, so a re-run of
+    /// the command sends a new response.
+    pub async fn delete(self) -> Result<(), serenity::Error> {
+        match self.0 {
+            ReplyHandleInner::Prefix { ctx, message } => {
+                message
+                    .channel_id
+                    .delete_message(ctx.discord, message.id)
+                    .await?;
+                if let Some(edit_tracker) = &ctx.framework.options().prefix_options.edit_tracker {
+                    edit_tracker.write().remove_bot_response(message.id);
+                }
+            }
+            ReplyHandleInner::SlashOriginal { ctx } => {
+                ctx.interaction
+                    .delete_original_interaction_response(ctx.discord)
+                    .await?;
+            }
+            ReplyHandleInner::SlashFollowup { ctx, message } => {
+                ctx.interaction
+                    .delete_followup_message(ctx.discord, message.id)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Makes mentions in the given text non-pinging by inserting a zero-width space after the `@`.
+/// Covers `@everyone`, `@here`, and user, nickname and role mentions.
+///
+/// ```rust
+/// assert_eq!(
+///     poise::sanitize_mentions("@everyone <@&123>"),
+///     "@\u{200B}everyone <@\u{200B}&123>",
+/// );
+/// ```
+pub fn sanitize_mentions(text: &str) -> String {
+    text.replace("@everyone", "@\u{200B}everyone")
+        .replace("@here", "@\u{200B}here")
+        .replace("<@", "<@\u{200B}")
+}
+
+/// Sets the embeds in the given builder data. The embed list is always sent, even if empty, so
+/// that the embeds of a reply fully replace the embeds of the message when editing.
+///
+/// Used by all send and edit paths, instead of the builders' embed methods, some of which append
+/// to the existing embeds or omit an empty embed list.
+pub(crate) fn replace_embeds(
+    builder: &mut std::collections::HashMap<&'static str, serde_json::Value>,
+    embeds: Vec<serenity::CreateEmbed>,
+) {
+    let embeds = embeds
+        .into_iter()
+        .map(|embed| {
+            serde_json::Value::Object(
+                embed
+                    .0
+                    .into_iter()
+                    .map(|(key, value)| (key.to_owned(), value))
+                    .collect(),
+            )
+        })
+        .collect();
+    builder.insert("embeds", serde_json::Value::Array(embeds));
+}
+
+/// Discord's message flag which hides the link previews of a message
+pub(crate) const SUPPRESS_EMBEDS_FLAG: u64 = 1 << 2;
+
+/// Adds [`SUPPRESS_EMBEDS_FLAG`] to the flags in the given builder data, keeping other flags
+/// like the ephemeral flag
+pub(crate) fn add_suppress_embeds_flag(
+    builder: &mut std::collections::HashMap<&'static str, serde_json::Value>,
+) {
+    let flags = builder
+        .get("flags")
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(0);
+    builder.insert(
+        "flags",
+        serde_json::Value::from(flags | SUPPRESS_EMBEDS_FLAG),
+    );
+}
+
+/// Maximum length of message content
+const MESSAGE_LENGTH_LIMIT: usize = 2000;
+
+/// Splits message content into chunks of at most `limit` bytes, preferably at line boundaries.
+/// Code blocks spanning multiple chunks are closed at the end of a chunk and reopened with the
+/// same language tag at the start of the next.
+fn split_content(content: &str, limit: usize) -> Vec<String> {
+    // Leave room for the closing and reopening code fences around overly long lines
+    let max_piece_len = limit.saturating_sub(100).max(limit / 2).max(1);
+
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    // Language tag of the code block that's open at the end of the current chunk
+    let mut open_code_block: Option<String> = None;
+    for line in content.split('\n') {
+        // Overly long lines can't be kept intact
+        let mut pieces = Vec::new();
+        let mut rest = line;
+        while rest.len() > max_piece_len {
+            let mut split_index = max_piece_len;
+            while !rest.is_char_boundary(split_index) {
+                split_index -= 1;
+            }
+            let (piece, remainder) = rest.split_at(split_index);
+            pieces.push(piece);
+            rest = remainder;
+        }
+        pieces.push(rest);
+
+        let mut open_code_block_after_line = open_code_block.clone();
+        for _ in 0..line.matches("```").count() {
+            open_code_block_after_line = match open_code_block_after_line {
+                Some(_) => None,
+                None => Some(
+                    line.trim_start()
+                        .strip_prefix("```")
+                        .filter(|rest| !rest.contains("```"))
+                        .unwrap_or("")
+                        .trim()
+                        .to_owned(),
+                ),
+            };
+        }
+
+        for (i, piece) in pieces.into_iter().enumerate() {
+            // The chunk needs room for a closing fence if a code block is open after this piece,
+            // including one that's opened by this line
+            let closing_fence_len =
+                if open_code_block.is_some() || open_code_block_after_line.is_some() {
+                    4
+                } else {
+                    0
+                };
+            if !chunk.is_empty() && chunk.len() + 1 + piece.len() + closing_fence_len > limit {
+                if open_code_block.is_some() {
+                    chunk.push_str("\n```");
+                }
+                chunks.push(std::mem::take(&mut chunk));
+                if let Some(language) = &open_code_block {
+                    chunk = format!("```{}\n", language);
+                }
+            }
+
+            // Continuations of overly long lines are appended without line break, and line breaks
+            // at chunk boundaries are dropped
+            if i == 0 && !chunk.is_empty() && !chunk.ends_with('\n') {
+                chunk.push('\n');
+            }
+            chunk.push_str(piece);
+        }
+
+        open_code_block = open_code_block_after_line;
+    }
+    chunks.push(chunk);
+    chunks
 }
 
-pub async fn send_reply<U, E>(
-    ctx: crate::Context<'_, U, E>,
+pub async fn send_reply<'ctx, U, E>(
+    ctx: crate::Context<'ctx, U, E>,
     builder: impl for<'a, 'b> FnOnce(&'a mut CreateReply<'b>) -> &'a mut CreateReply<'b>,
-) -> Result<(), serenity::Error> {
+) -> Result<ReplyHandle<'ctx, U, E>, serenity::Error> {
     match ctx {
         crate::Context::Prefix(ctx) => crate::send_prefix_reply(ctx, builder).await,
         crate::Context::Slash(ctx) => crate::send_slash_reply(ctx, builder).await,
     }
 }
 
-pub async fn say_reply<U, E>(
-    ctx: crate::Context<'_, U, E>,
-    text: String,
-) -> Result<(), serenity::Error> {
-    send_reply(ctx, |m| m.content(text)).await
+pub async fn say_reply<'ctx, U, E>(
+    ctx: crate::Context<'ctx, U, E>,
+    text: impl Into<String>,
+) -> Result<ReplyHandle<'ctx, U, E>, serenity::Error> {
+    send_reply(ctx, |m| m.content(text.into())).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_mentions() {
+        assert_eq!(
+            sanitize_mentions("hi @here, <@123> and <@!456>"),
+            "hi @\u{200B}here, <@\u{200B}123> and <@\u{200B}!456>"
+        );
+        assert_eq!(sanitize_mentions("mail@example.com"), "mail@example.com");
+    }
+
+    #[test]
+    fn test_split_content() {
+        assert_eq!(split_content("short", 2000), vec!["short".to_owned()]);
+
+        let content = format!("intro\n```rust\n{}```\noutro", "let x = 1;\n".repeat(20));
+        let chunks = split_content(&content, 100);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.len() <= 100, "chunk too long: {:?}", chunk);
+            assert_eq!(
+                chunk.matches("```").count() % 2,
+                0,
+                "unbalanced: {:?}",
+                chunk
+            );
+        }
+        assert!(chunks[1].starts_with("```rust\n"));
+        assert_eq!(
+            chunks.concat().matches("let x = 1;").count(),
+            20,
+            "content was lost"
+        );
+
+        // The line opening a code block must leave room for closing it
+        let content = format!("{}\n```rust\nlet x = 1;\nmore", "a".repeat(90));
+        let chunks = split_content(&content, 100);
+        assert!(
+            chunks.iter().all(|chunk| chunk.len() <= 100),
+            "{:?}",
+            chunks
+        );
+        assert_eq!(chunks[0], "a".repeat(90));
+
+        let chunks = split_content(&"ä".repeat(150), 100);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 100));
+        assert_eq!(chunks.concat(), "ä".repeat(150));
+    }
 }
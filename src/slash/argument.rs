@@ -10,6 +10,16 @@ pub enum SlashArgError {
     CommandStructureMismatch(&'static str),
     Parse(Box<dyn std::error::Error + Send + Sync>),
     IntegerOutOfBounds,
+    /// The value lies outside the bounds declared on the parameter
+    OutOfRange {
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    /// The value of the named parameter is invalid
+    Parameter {
+        name: &'static str,
+        error: Box<SlashArgError>,
+    },
 }
 impl std::fmt::Display for SlashArgError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -23,6 +33,15 @@ impl std::fmt::Display for SlashArgError {
             }
             Self::Parse(e) => write!(f, "Failed to parse argument: {}", e),
             Self::IntegerOutOfBounds => write!(f, "Integer out of bounds for target type"),
+            Self::OutOfRange { min, max } => match (min, max) {
+                (Some(min), Some(max)) => write!(f, "Value must be between {} and {}", min, max),
+                (Some(min), None) => write!(f, "Value must be at least {}", min),
+                (None, Some(max)) => write!(f, "Value must be at most {}", max),
+                (None, None) => write!(f, "Value out of range"),
+            },
+            Self::Parameter { name, error } => {
+                write!(f, "Invalid value for parameter `{}`: {}", name, error)
+            }
         }
     }
 }
@@ -32,6 +51,84 @@ impl std::error::Error for SlashArgError {
             Self::Parse(e) => Some(&**e),
             Self::CommandStructureMismatch(_) => None,
             Self::IntegerOutOfBounds => None,
+            Self::OutOfRange { .. } => None,
+            Self::Parameter { error, .. } => Some(&**error),
+        }
+    }
+}
+
+/// The arguments of a slash command invocation could not be parsed, for example because the
+/// command was registered with an outdated signature.
+///
+/// Returned from the actions generated by `#[poise::command]` and passed to `on_error`. By then,
+/// the user has already been sent an ephemeral error message.
+#[derive(Debug)]
+pub struct SlashArgumentParseError {
+    /// Qualified name of the invoked command, including subcommands
+    pub command_name: String,
+    /// Name of the parameter that failed to parse, if known
+    pub parameter: Option<&'static str>,
+    /// Raw value of that parameter as received from Discord, if any
+    pub value: Option<serde_json::Value>,
+    /// The underlying error
+    pub error: SlashArgError,
+}
+
+impl std::fmt::Display for SlashArgumentParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid arguments for `/{}`: {}",
+            self.command_name, self.error
+        )?;
+        if let Some(value) = &self.value {
+            write!(f, " (received {})", value)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SlashArgumentParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl SlashArgumentParseError {
+    /// Collects the details of an argument parse error and responds to the interaction with an
+    /// ephemeral error message.
+    ///
+    /// Used by the `#[poise::command]` macro.
+    #[doc(hidden)]
+    pub async fn respond<U, E>(
+        ctx: crate::SlashContext<'_, U, E>,
+        args: &[serenity::ApplicationCommandInteractionDataOption],
+        error: SlashArgError,
+    ) -> Self {
+        let parameter = match &error {
+            SlashArgError::Parameter { name, .. } => Some(*name),
+            _ => None,
+        };
+        let value = parameter.and_then(|name| {
+            args.iter()
+                .find(|arg| arg.name == name)
+                .and_then(|arg| arg.value.clone())
+        });
+
+        let response = error.to_string();
+        if let Err(e) = crate::send_slash_reply(ctx, |f| f.content(response).ephemeral(true)).await
+        {
+            println!(
+                "Failed to respond to invalid slash command arguments: {}",
+                e
+            );
+        }
+
+        Self {
+            command_name: ctx.qualified_command_name(),
+            parameter,
+            value,
+            error,
         }
     }
 }
@@ -57,6 +154,45 @@ pub trait SlashArgument: Sized {
     ) -> &mut serenity::CreateApplicationCommandOption;
 }
 
+/// A parameter with a fixed set of choices, like an enum. Derive it with
+/// `#[derive(poise::SlashChoiceParameter)]` on an enum with unit variants. Each variant is
+/// displayed under its name, or under the name given via `#[name = "..."]`.
+///
+/// The derive macro also implements [`SlashArgument`], [`std::str::FromStr`], and
+/// [`crate::PopArgument`], so the enum can be used in slash and prefix commands alike.
+pub trait SlashChoiceParameter: Sized {
+    /// Display names of all choices, in declaration order
+    fn choice_names() -> &'static [&'static str];
+
+    /// Returns the choice at the given position of [`Self::choice_names`]
+    fn from_index(index: usize) -> Option<Self>;
+
+    /// Returns the choice with the given display name, compared case-insensitively
+    fn from_name(name: &str) -> Option<Self> {
+        let index = Self::choice_names()
+            .iter()
+            .position(|choice_name| choice_name.eq_ignore_ascii_case(name))?;
+        Self::from_index(index)
+    }
+
+    /// Returns the choice for a select menu option value, as created by
+    /// [`Self::create_select_options`]
+    fn from_component_value(value: &str) -> Option<Self> {
+        Self::from_index(value.parse().ok()?)
+    }
+
+    /// Adds one select menu option per choice. The option values can be mapped back into choices
+    /// with [`Self::from_component_value`] or [`crate::ComponentContext::selected_choices`].
+    fn create_select_options(
+        builder: &mut serenity::CreateSelectMenuOptions,
+    ) -> &mut serenity::CreateSelectMenuOptions {
+        for (index, name) in Self::choice_names().iter().enumerate() {
+            builder.create_option(|f| f.label(*name).value(index));
+        }
+        builder
+    }
+}
+
 /// Implemented for all types that can be used as a function parameter in a slash command.
 ///
 /// Currently marked `#[doc(hidden)]` because implementing this trait requires some jank due to a
@@ -69,6 +205,7 @@ pub trait SlashArgumentHack<T> {
         ctx: &serenity::Context,
         guild: Option<serenity::GuildId>,
         channel: Option<serenity::ChannelId>,
+        resolved: &serenity::ApplicationCommandInteractionDataResolved,
         value: &serde_json::Value,
     ) -> Result<T, SlashArgError>;
 
@@ -90,6 +227,7 @@ where
         ctx: &serenity::Context,
         guild: Option<serenity::GuildId>,
         channel: Option<serenity::ChannelId>,
+        _: &serenity::ApplicationCommandInteractionDataResolved,
         value: &serde_json::Value,
     ) -> Result<T, SlashArgError> {
         let string = value
@@ -116,6 +254,7 @@ impl<T: TryFrom<i64> + Send + Sync> SlashArgumentHack<T> for &PhantomData<T> {
         _: &serenity::Context,
         _: Option<serenity::GuildId>,
         _: Option<serenity::ChannelId>,
+        _: &serenity::ApplicationCommandInteractionDataResolved,
         value: &serde_json::Value,
     ) -> Result<T, SlashArgError> {
         value
@@ -141,6 +280,7 @@ impl SlashArgumentHack<f32> for &&PhantomData<f32> {
         _: &serenity::Context,
         _: Option<serenity::GuildId>,
         _: Option<serenity::ChannelId>,
+        _: &serenity::ApplicationCommandInteractionDataResolved,
         value: &serde_json::Value,
     ) -> Result<f32, SlashArgError> {
         Ok(value
@@ -163,6 +303,7 @@ impl SlashArgumentHack<f64> for &&PhantomData<f64> {
         _: &serenity::Context,
         _: Option<serenity::GuildId>,
         _: Option<serenity::ChannelId>,
+        _: &serenity::ApplicationCommandInteractionDataResolved,
         value: &serde_json::Value,
     ) -> Result<f64, SlashArgError> {
         value
@@ -185,6 +326,7 @@ impl<T: SlashArgument + Sync> SlashArgumentHack<T> for &&PhantomData<T> {
         ctx: &serenity::Context,
         guild: Option<serenity::GuildId>,
         channel: Option<serenity::ChannelId>,
+        _: &serenity::ApplicationCommandInteractionDataResolved,
         value: &serde_json::Value,
     ) -> Result<T, SlashArgError> {
         <T as SlashArgument>::extract(ctx, guild, channel, value).await
@@ -208,11 +350,12 @@ macro_rules! impl_slash_argument {
                 ctx: &serenity::Context,
                 guild: Option<serenity::GuildId>,
                 channel: Option<serenity::ChannelId>,
+                resolved: &serenity::ApplicationCommandInteractionDataResolved,
                 value: &serde_json::Value,
             ) -> Result<$type, SlashArgError> {
                 // We can parse IDs by falling back to the generic serenity::ArgumentConvert impl
                 PhantomData::<$type>
-                    .extract(ctx, guild, channel, value)
+                    .extract(ctx, guild, channel, resolved, value)
                     .await
             }
 
@@ -225,27 +368,165 @@ macro_rules! impl_slash_argument {
         }
     };
 }
-impl_slash_argument!(serenity::Member, User);
-impl_slash_argument!(serenity::User, User);
 impl_slash_argument!(serenity::Channel, Channel);
 impl_slash_argument!(serenity::GuildChannel, Channel);
-impl_slash_argument!(serenity::Role, Role);
+
+/// Parses the snowflake ID that Discord sends as the value of user, channel, role, mentionable,
+/// and attachment parameters
+fn extract_id(value: &serde_json::Value) -> Result<u64, SlashArgError> {
+    value
+        .as_str()
+        .ok_or(SlashArgError::CommandStructureMismatch(
+            "expected snowflake string",
+        ))?
+        .parse()
+        .map_err(|e| SlashArgError::Parse(Box::new(e)))
+}
+
+/// Error for when the interaction lacks the resolved data for an ID parameter
+fn missing_resolved_data(kind: &str, id: u64) -> SlashArgError {
+    SlashArgError::Parse(format!("no resolved data for {} {}", kind, id).into())
+}
+
+/// A user or a role, as accepted by a mentionable slash command parameter
+#[derive(Debug, Clone)]
+pub enum Mentionable {
+    User(serenity::User),
+    Role(serenity::Role),
+}
+
+// Implement slash argument for a model type that is resolved from the interaction's resolved data
+macro_rules! impl_resolved_slash_argument {
+    ($type:ty, $slash_param_type:ident, |$guild:ident, $resolved:ident, $id:ident| $extract:expr) => {
+        #[async_trait::async_trait]
+        impl SlashArgumentHack<$type> for &&PhantomData<$type> {
+            async fn extract(
+                self,
+                _: &serenity::Context,
+                $guild: Option<serenity::GuildId>,
+                _: Option<serenity::ChannelId>,
+                $resolved: &serenity::ApplicationCommandInteractionDataResolved,
+                value: &serde_json::Value,
+            ) -> Result<$type, SlashArgError> {
+                let $id = extract_id(value)?;
+                $extract
+            }
+
+            fn create(
+                self,
+                builder: &mut serenity::CreateApplicationCommandOption,
+            ) -> &mut serenity::CreateApplicationCommandOption {
+                builder.kind(serenity::ApplicationCommandOptionType::$slash_param_type)
+            }
+        }
+    };
+}
+impl_resolved_slash_argument!(serenity::User, User, |_guild, resolved, id| {
+    resolved
+        .users
+        .get(&serenity::UserId(id))
+        .cloned()
+        .ok_or_else(|| missing_resolved_data("user", id))
+});
+impl_resolved_slash_argument!(serenity::Member, User, |guild, resolved, id| {
+    let (user, partial_member) = match (
+        resolved.users.get(&serenity::UserId(id)),
+        resolved.members.get(&serenity::UserId(id)),
+    ) {
+        (Some(user), Some(partial_member)) => (user, partial_member),
+        _ => return Err(missing_resolved_data("member", id)),
+    };
+    let guild_id = guild.ok_or_else(|| missing_resolved_data("member", id))?;
+
+    // The resolved member lacks the user and guild ID, which are supplied separately. Going
+    // through JSON spares us from listing every field of Member
+    let mut member =
+        serde_json::to_value(partial_member).map_err(|e| SlashArgError::Parse(Box::new(e)))?;
+    member["user"] = serde_json::to_value(user).map_err(|e| SlashArgError::Parse(Box::new(e)))?;
+    member["guild_id"] = guild_id.0.to_string().into();
+    serde_json::from_value(member).map_err(|e| SlashArgError::Parse(Box::new(e)))
+});
+impl_resolved_slash_argument!(serenity::PartialChannel, Channel, |_guild, resolved, id| {
+    resolved
+        .channels
+        .get(&serenity::ChannelId(id))
+        .cloned()
+        .ok_or_else(|| missing_resolved_data("channel", id))
+});
+impl_resolved_slash_argument!(serenity::Role, Role, |_guild, resolved, id| {
+    resolved
+        .roles
+        .get(&serenity::RoleId(id))
+        .cloned()
+        .ok_or_else(|| missing_resolved_data("role", id))
+});
+impl_resolved_slash_argument!(serenity::Attachment, Attachment, |_guild, resolved, id| {
+    resolved
+        .attachments
+        .get(&serenity::AttachmentId(id))
+        .cloned()
+        .ok_or_else(|| missing_resolved_data("attachment", id))
+});
+impl_resolved_slash_argument!(Mentionable, Mentionable, |_guild, resolved, id| {
+    if let Some(user) = resolved.users.get(&serenity::UserId(id)) {
+        Ok(Mentionable::User(user.clone()))
+    } else if let Some(role) = resolved.roles.get(&serenity::RoleId(id)) {
+        Ok(Mentionable::Role(role.clone()))
+    } else {
+        Err(missing_resolved_data("mentionable", id))
+    }
+});
+
+/// Checks that the numeric argument with the given name lies within the given bounds. Missing
+/// arguments pass the check.
+///
+/// Used by the `#[poise::command]` macro for parameters declared with `#[min = ...]` or
+/// `#[max = ...]`.
+pub fn check_slash_arg_bounds(
+    args: &[serenity::ApplicationCommandInteractionDataOption],
+    name: &'static str,
+    min: Option<f64>,
+    max: Option<f64>,
+) -> Result<(), SlashArgError> {
+    let value = match args
+        .iter()
+        .find(|arg| arg.name == name)
+        .and_then(|arg| arg.value.as_ref())
+    {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let invalid = |error| SlashArgError::Parameter {
+        name,
+        error: Box::new(error),
+    };
+
+    let value = value
+        .as_f64()
+        .ok_or_else(|| invalid(SlashArgError::CommandStructureMismatch("expected number")))?;
+    if min.map_or(false, |min| value < min) || max.map_or(false, |max| value > max) {
+        return Err(invalid(SlashArgError::OutOfRange { min, max }));
+    }
+    Ok(())
+}
 
 #[doc(hidden)]
 #[macro_export]
 macro_rules! _parse_slash {
     // Extract Option<T>
-    ($ctx:ident, $guild_id:ident, $channel_id:ident, $args:ident => $name:ident: Option<$type:ty $(,)*>) => {
+    ($ctx:ident, $guild_id:ident, $channel_id:ident, $resolved:ident, $args:ident => $name:ident: Option<$type:ty $(,)*>) => {
         #[allow(clippy::eval_order_dependence)]
         if let Some(arg) = $args.iter().find(|arg| arg.name == stringify!($name)) {
-            let arg = arg.value
-            .as_ref()
-            .ok_or($crate::SlashArgError::CommandStructureMismatch("expected argument value"))?;
-            Some(
-                (&&&&&std::marker::PhantomData::<$type>)
-                .extract($ctx, $guild_id, Some($channel_id), arg)
-                .await?
-            )
+            let result = match &arg.value {
+                Some(arg) => (&&&&&std::marker::PhantomData::<$type>)
+                    .extract($ctx, $guild_id, Some($channel_id), $resolved, arg)
+                    .await,
+                None => Err($crate::SlashArgError::CommandStructureMismatch("expected argument value")),
+            };
+            Some(result.map_err(|error| $crate::SlashArgError::Parameter {
+                name: stringify!($name),
+                error: Box::new(error),
+            })?)
         } else {
             None
         }
@@ -253,39 +534,102 @@ macro_rules! _parse_slash {
 
     // Extract Vec<T> (delegating to Option<T> because slash commands don't support variadic
     // arguments right now)
-    ($ctx:ident, $guild_id:ident, $channel_id:ident, $args:ident => $name:ident: Vec<$type:ty $(,)*>) => {
-        match $crate::_parse_slash!($ctx, $guild_id, $channel_id, $args => $name: Option<$type>) {
+    ($ctx:ident, $guild_id:ident, $channel_id:ident, $resolved:ident, $args:ident => $name:ident: Vec<$type:ty $(,)*>) => {
+        match $crate::_parse_slash!($ctx, $guild_id, $channel_id, $resolved, $args => $name: Option<$type>) {
             Some(value) => vec![value],
             None => vec![],
         }
     };
 
     // Extract #[flag]
-    ($ctx:ident, $guild_id:ident, $channel_id:ident, $args:ident => $name:ident: FLAG) => {
-        $crate::_parse_slash!($ctx, $guild_id, $channel_id, $args => $name: Option<bool>)
+    ($ctx:ident, $guild_id:ident, $channel_id:ident, $resolved:ident, $args:ident => $name:ident: FLAG) => {
+        $crate::_parse_slash!($ctx, $guild_id, $channel_id, $resolved, $args => $name: Option<bool>)
             .unwrap_or(false)
     };
 
     // Extract T
-    ($ctx:ident, $guild_id:ident, $channel_id:ident, $args:ident => $name:ident: $($type:tt)*) => {
-        $crate::_parse_slash!($ctx, $guild_id, $channel_id, $args => $name: Option<$($type)*>)
-            .ok_or($crate::SlashArgError::CommandStructureMismatch("a required argument is missing"))?
+    ($ctx:ident, $guild_id:ident, $channel_id:ident, $resolved:ident, $args:ident => $name:ident: $($type:tt)*) => {
+        $crate::_parse_slash!($ctx, $guild_id, $channel_id, $resolved, $args => $name: Option<$($type)*>)
+            .ok_or_else(|| $crate::SlashArgError::Parameter {
+                name: stringify!($name),
+                error: Box::new($crate::SlashArgError::CommandStructureMismatch("a required argument is missing")),
+            })?
     };
 }
 
 #[macro_export]
 macro_rules! parse_slash_args {
-    ($ctx:expr, $guild_id:expr, $channel_id:expr, $args:expr => $(
+    ($ctx:expr, $guild_id:expr, $channel_id:expr, $resolved:expr, $args:expr => $(
         ( $name:ident: $($type:tt)* )
     ),* $(,)? ) => {
         async /* not move! */ {
             use $crate::SlashArgumentHack;
 
-            let (ctx, guild_id, channel_id, args) = ($ctx, $guild_id, $channel_id, $args);
+            let (ctx, guild_id, channel_id, resolved, args) =
+                ($ctx, $guild_id, $channel_id, $resolved, $args);
 
             Ok::<_, $crate::SlashArgError>(( $(
-                $crate::_parse_slash!( ctx, guild_id, channel_id, args => $name: $($type)* ),
+                $crate::_parse_slash!( ctx, guild_id, channel_id, resolved, args => $name: $($type)* ),
             )* ))
         }
     };
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_slash_arg_bounds() {
+        let args: Vec<serenity::ApplicationCommandInteractionDataOption> =
+            serde_json::from_value(serde_json::json!([
+                { "name": "level", "type": 4, "value": 250 },
+                { "name": "ratio", "type": 10, "value": 0.5 },
+                { "name": "text", "type": 3, "value": "abc" },
+            ]))
+            .unwrap();
+
+        assert!(check_slash_arg_bounds(&args, "level", Some(0.0), Some(300.0)).is_ok());
+        assert!(check_slash_arg_bounds(&args, "ratio", Some(0.0), Some(1.0)).is_ok());
+        assert!(check_slash_arg_bounds(&args, "missing", Some(0.0), Some(1.0)).is_ok());
+
+        let error = check_slash_arg_bounds(&args, "level", Some(0.0), Some(200.0)).unwrap_err();
+        assert!(matches!(
+            error,
+            SlashArgError::Parameter { name: "level", ref error }
+                if matches!(**error, SlashArgError::OutOfRange { .. })
+        ));
+        assert!(check_slash_arg_bounds(&args, "ratio", Some(0.75), None).is_err());
+        assert!(matches!(
+            check_slash_arg_bounds(&args, "text", None, Some(5.0)),
+            Err(SlashArgError::Parameter { name: "text", .. })
+        ));
+    }
+
+    #[test]
+    fn test_choice_component_value() {
+        #[derive(Debug, PartialEq)]
+        enum Color {
+            Red,
+            Blue,
+        }
+        impl SlashChoiceParameter for Color {
+            fn choice_names() -> &'static [&'static str] {
+                &["Red", "Blue"]
+            }
+
+            fn from_index(index: usize) -> Option<Self> {
+                match index {
+                    0 => Some(Self::Red),
+                    1 => Some(Self::Blue),
+                    _ => None,
+                }
+            }
+        }
+
+        assert_eq!(Color::from_component_value("0"), Some(Color::Red));
+        assert_eq!(Color::from_component_value("1"), Some(Color::Blue));
+        assert_eq!(Color::from_component_value("2"), None);
+        assert_eq!(Color::from_component_value("Red"), None);
+    }
+}
@@ -6,59 +6,695 @@ pub use structs::*;
 mod argument;
 pub use argument::*;
 
+mod modal;
+pub use modal::*;
+
 use crate::serenity_prelude as serenity;
 
+/// Builds the application command registration payload for the slash implementations of the
+/// given commands.
+///
+/// Commands without slash implementation and commands marked
+/// [`SlashCommandOptions::prefix_only`] are skipped.
+pub fn create_application_commands<U, E>(
+    commands: &[crate::Command<U, E>],
+) -> serenity::CreateApplicationCommands {
+    create_application_commands_filtered(commands, |_| true)
+}
+
+/// Like [`create_application_commands`], but only includes the top-level commands for which the
+/// filter returns true.
+pub fn create_application_commands_filtered<U, E>(
+    commands: &[crate::Command<U, E>],
+    filter: impl Fn(&crate::Command<U, E>) -> bool,
+) -> serenity::CreateApplicationCommands {
+    let mut commands_builder = serenity::CreateApplicationCommands::default();
+    for command in commands.iter().filter(|command| filter(command)) {
+        for (command, subcommands) in structs::slash_commands(std::slice::from_ref(command)) {
+            commands_builder.create_application_command(|f| command.create(subcommands, f));
+        }
+    }
+    commands_builder
+}
+
+/// Fills in the localization fields of the given commands, their parameters and their
+/// subcommands with translations from the localizer. See [`SlashFrameworkOptions::localizer`]
+/// for the translation keys.
+pub fn localize_commands<U, E>(
+    commands: &mut [crate::Command<U, E>],
+    locales: &[String],
+    localizer: fn(&str, &str) -> Option<String>,
+) {
+    fn localize(
+        localizations: &mut std::collections::HashMap<String, String>,
+        locales: &[String],
+        localizer: fn(&str, &str) -> Option<String>,
+        key: &str,
+    ) {
+        for locale in locales {
+            if localizations.contains_key(locale) {
+                continue;
+            }
+            if let Some(translation) = localizer(locale, key) {
+                localizations.insert(locale.clone(), translation);
+            }
+        }
+    }
+
+    fn localize_command<U, E>(
+        command_meta: &mut crate::Command<U, E>,
+        path: &str,
+        locales: &[String],
+        localizer: fn(&str, &str) -> Option<String>,
+    ) {
+        let command = match &mut command_meta.slash_action {
+            Some(x) => x,
+            None => return,
+        };
+        let path = match path {
+            "" => command.name.to_owned(),
+            path => format!("{}.{}", path, command.name),
+        };
+        let key = |field: &str| format!("{}.{}", path, field);
+
+        localize(
+            &mut command.name_localizations,
+            locales,
+            localizer,
+            &key("name"),
+        );
+        localize(
+            &mut command.description_localizations,
+            locales,
+            localizer,
+            &key("description"),
+        );
+
+        for parameter in &mut command.parameters {
+            let key = |field: &str| format!("{}.{}.{}", path, parameter.name, field);
+            let (name_key, description_key) = (key("name"), key("description"));
+            localize(
+                &mut parameter.name_localizations,
+                locales,
+                localizer,
+                &name_key,
+            );
+            localize(
+                &mut parameter.description_localizations,
+                locales,
+                localizer,
+                &description_key,
+            );
+
+            // The choices are only known to the builder
+            let mut option = serenity::CreateApplicationCommandOption::default();
+            (parameter.builder)(&mut option);
+            let choices = option.0.get("choices").and_then(|c| c.as_array());
+            for choice_name in choices
+                .into_iter()
+                .flatten()
+                .filter_map(|c| c["name"].as_str())
+            {
+                let key = format!("{}.{}.choices.{}", path, parameter.name, choice_name);
+                let localizations = parameter
+                    .choice_localizations
+                    .entry(choice_name.to_owned())
+                    .or_default();
+                localize(localizations, locales, localizer, &key);
+                if localizations.is_empty() {
+                    parameter.choice_localizations.remove(choice_name);
+                }
+            }
+        }
+
+        for subcommand in &mut command_meta.subcommands {
+            localize_command(subcommand, &path, locales, localizer);
+        }
+    }
+
+    for command in commands {
+        localize_command(command, "", locales, localizer);
+    }
+}
+
+/// Returned when registering application commands fails
+#[derive(Debug)]
+pub enum RegistrationError {
+    /// A command or parameter violates Discord's constraints. Detected before sending anything to
+    /// Discord
+    InvalidCommand {
+        /// Name of the offending command or parameter, as far as it's known
+        name: String,
+        /// What's wrong with it
+        reason: &'static str,
+    },
+    /// More commands of one type than Discord allows
+    TooManyCommands {
+        /// The type of command, e.g. "user context menu commands"
+        kind: &'static str,
+        count: usize,
+        max_count: usize,
+    },
+    /// The registration request failed
+    Http(serenity::Error),
+}
+
+impl std::fmt::Display for RegistrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidCommand { name, reason } => {
+                write!(f, "invalid application command `{}`: {}", name, reason)
+            }
+            Self::TooManyCommands {
+                kind,
+                count,
+                max_count,
+            } => write!(
+                f,
+                "too many {}: {} given, but at most {} are allowed",
+                kind, count, max_count
+            ),
+            Self::Http(e) => write!(f, "failed to register application commands: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RegistrationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidCommand { .. } => None,
+            Self::TooManyCommands { .. } => None,
+            Self::Http(e) => Some(e),
+        }
+    }
+}
+
+impl From<serenity::Error> for RegistrationError {
+    fn from(e: serenity::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+/// Checks that there are no more application commands of each type than Discord allows
+fn validate_command_counts(commands: &[serde_json::Value]) -> Result<(), RegistrationError> {
+    // (type, what to call it, maximum count)
+    const LIMITS: [(u64, &str, usize); 3] = [
+        (1, "slash commands", 100),
+        (2, "user context menu commands", 5),
+        (3, "message context menu commands", 5),
+    ];
+
+    for &(kind, kind_name, max_count) in &LIMITS {
+        let count = commands
+            .iter()
+            // Commands without explicit type are slash commands
+            .filter(|command| command["type"].as_u64().unwrap_or(1) == kind)
+            .count();
+        if count > max_count {
+            return Err(RegistrationError::TooManyCommands {
+                kind: kind_name,
+                count,
+                max_count,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks application commands and their options against Discord's constraints, so that users
+/// get a helpful error instead of an opaque HTTP 400. `path` is the qualified name of the parent
+/// command, if any
+fn validate_commands(commands: &[serde_json::Value], path: &str) -> Result<(), RegistrationError> {
+    const MAX_OPTIONS: usize = 25;
+    const MAX_CHOICES: usize = 25;
+
+    let mut optional_option_seen = false;
+    for command in commands {
+        let name = command["name"].as_str().unwrap_or("");
+        let qualified_name = if path.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{} {}", path, name)
+        };
+        let invalid = |reason| RegistrationError::InvalidCommand {
+            name: qualified_name.clone(),
+            reason,
+        };
+
+        let name_length = name.chars().count();
+        if name_length < 1 || name_length > 32 {
+            return Err(invalid("name must be 1-32 characters long"));
+        }
+
+        // Context menu commands don't have descriptions, and their names may contain spaces and
+        // uppercase letters
+        if let Some(description) = command["description"].as_str() {
+            if !name
+                .chars()
+                .all(|c| (c.is_alphanumeric() || c == '-' || c == '_') && !c.is_uppercase())
+            {
+                return Err(invalid(
+                    "name must only contain lowercase letters, numbers, dashes and underscores",
+                ));
+            }
+
+            let description_length = description.chars().count();
+            if description_length < 1 || description_length > 100 {
+                return Err(invalid("description must be 1-100 characters long"));
+            }
+        } else if !path.is_empty() {
+            return Err(invalid("options must have a description"));
+        }
+
+        // Subcommands and subcommand groups (types 1 and 2) don't have the required field
+        if !path.is_empty() && command["type"].as_u64().map_or(false, |kind| kind > 2) {
+            if command["required"].as_bool().unwrap_or(false) {
+                if optional_option_seen {
+                    return Err(invalid(
+                        "required options must come before optional options",
+                    ));
+                }
+            } else {
+                optional_option_seen = true;
+            }
+        }
+
+        if let Some(choices) = command["choices"].as_array() {
+            if choices.len() > MAX_CHOICES {
+                return Err(invalid("options can have at most 25 choices"));
+            }
+        }
+
+        if let Some(options) = command["options"].as_array() {
+            if options.len() > MAX_OPTIONS {
+                return Err(invalid("commands can have at most 25 options"));
+            }
+            validate_commands(options, &qualified_name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks the generated registration payload against Discord's constraints
+fn validate_payload(commands: &[serde_json::Value]) -> Result<(), RegistrationError> {
+    validate_command_counts(commands)?;
+    validate_commands(commands, "")
+}
+
+/// Checks the given commands against Discord's constraints on names, descriptions, and the number
+/// and order of options and choices, without registering anything.
+///
+/// The registration functions run this check too. Additionally, [`crate::Framework::new`] runs it
+/// in debug builds, so that invalid commands are noticed before deploying.
+pub fn validate_application_commands<U, E>(
+    commands: &[crate::Command<U, E>],
+) -> Result<(), RegistrationError> {
+    validate_payload(&create_application_commands(commands).0)
+}
+
+/// Registers the given slash commands in a single guild, replacing all existing guild commands.
+/// Commands [restricted](SlashCommandOptions::guilds) to other guilds are skipped.
+///
+/// Guild commands are available immediately, which makes this useful during development.
+pub async fn register_in_guild<U, E>(
+    http: &serenity::Http,
+    commands: &[crate::Command<U, E>],
+    guild_id: serenity::GuildId,
+) -> Result<Vec<serenity::ApplicationCommand>, RegistrationError> {
+    register_in_guild_filtered(http, commands, guild_id, |command| {
+        command.is_available_in(Some(guild_id))
+    })
+    .await
+}
+
+/// Registers the top-level commands for which the filter returns true in a single guild,
+/// replacing all existing guild commands. Useful to register different command sets per guild.
+pub async fn register_in_guild_filtered<U, E>(
+    http: &serenity::Http,
+    commands: &[crate::Command<U, E>],
+    guild_id: serenity::GuildId,
+    filter: impl Fn(&crate::Command<U, E>) -> bool,
+) -> Result<Vec<serenity::ApplicationCommand>, RegistrationError> {
+    let commands_builder = create_application_commands_filtered(commands, filter);
+    validate_payload(&commands_builder.0)?;
+    let json_value = serde_json::Value::Array(commands_builder.0);
+    Ok(http
+        .create_guild_application_commands(guild_id.0, &json_value)
+        .await?)
+}
+
+/// Registers the commands [restricted](SlashCommandOptions::guilds) to certain guilds in each of
+/// those guilds, replacing the existing guild commands. Complements [`register_globally`], which
+/// registers all other commands.
+pub async fn register_guild_specific<U, E>(
+    http: &serenity::Http,
+    commands: &[crate::Command<U, E>],
+) -> Result<(), RegistrationError> {
+    let mut guild_ids = commands
+        .iter()
+        .filter_map(|command| command.slash_guilds())
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>();
+    guild_ids.sort_unstable();
+    guild_ids.dedup();
+
+    for guild_id in guild_ids {
+        register_in_guild_filtered(http, commands, guild_id, |command| {
+            command.slash_guilds().is_some() && command.is_available_in(Some(guild_id))
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+/// Registers the given slash commands globally, replacing all existing global commands.
+/// Commands [restricted](SlashCommandOptions::guilds) to certain guilds are skipped.
+///
+/// Global commands may take up to an hour to show up in all guilds.
+pub async fn register_globally<U, E>(
+    http: &serenity::Http,
+    commands: &[crate::Command<U, E>],
+) -> Result<Vec<serenity::ApplicationCommand>, RegistrationError> {
+    let commands_builder =
+        create_application_commands_filtered(commands, |command| command.slash_guilds().is_none());
+    validate_payload(&commands_builder.0)?;
+    let json_value = serde_json::Value::Array(commands_builder.0);
+    Ok(http.create_global_application_commands(&json_value).await?)
+}
+
+/// Outcome of [`register_if_changed`], comparing the previously registered commands with the new
+/// ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RegistrationSummary {
+    /// Commands that weren't registered before
+    pub created: usize,
+    /// Commands that were registered before, but differed
+    pub updated: usize,
+    /// Registered commands that don't exist anymore
+    pub deleted: usize,
+    /// Commands that were already registered as is
+    pub unchanged: usize,
+}
+
+impl RegistrationSummary {
+    /// Whether the registered commands were overwritten
+    pub fn changed(&self) -> bool {
+        self.created + self.updated + self.deleted > 0
+    }
+}
+
+/// Reduces a command or option to the fields that matter for comparison, filling in Discord's
+/// defaults, so that commands fetched from Discord compare equal to freshly generated ones
+fn normalize_command(value: &serde_json::Value) -> serde_json::Value {
+    let mut normalized = serde_json::Map::new();
+    normalized.insert("type".into(), value["type"].as_u64().unwrap_or(1).into());
+    for &key in &["name", "description"] {
+        let field = value[key].as_str().unwrap_or("");
+        normalized.insert(key.into(), field.into());
+    }
+    // Discord sends null if there are no localizations
+    for &key in &["name_localizations", "description_localizations"] {
+        if let Some(localizations) = value[key].as_object().filter(|l| !l.is_empty()) {
+            normalized.insert(key.into(), localizations.clone().into());
+        }
+    }
+    normalized.insert(
+        "dm_permission".into(),
+        value["dm_permission"].as_bool().unwrap_or(true).into(),
+    );
+    // Sent as a string of the permission bits
+    if let Some(permissions) = value["default_member_permissions"].as_str() {
+        if permissions != "0" {
+            normalized.insert("default_member_permissions".into(), permissions.into());
+        }
+    }
+    for &key in &["required", "autocomplete"] {
+        let field = value[key].as_bool().unwrap_or(false);
+        normalized.insert(key.into(), field.into());
+    }
+    // Integers may come back as floats
+    for &key in &["min_value", "max_value"] {
+        if let Some(bound) = value[key].as_f64() {
+            normalized.insert(key.into(), bound.into());
+        }
+    }
+    if let Some(choices) = value["choices"].as_array() {
+        let choices = choices
+            .iter()
+            .map(|choice| {
+                let mut normalized = serde_json::json!({
+                    "name": choice["name"],
+                    "value": choice["value"],
+                });
+                if let Some(localizations) = choice["name_localizations"]
+                    .as_object()
+                    .filter(|l| !l.is_empty())
+                {
+                    normalized["name_localizations"] = localizations.clone().into();
+                }
+                normalized
+            })
+            .collect::<Vec<_>>();
+        if !choices.is_empty() {
+            normalized.insert("choices".into(), choices.into());
+        }
+    }
+    if let Some(options) = value["options"].as_array() {
+        let options = options.iter().map(normalize_command).collect::<Vec<_>>();
+        if !options.is_empty() {
+            normalized.insert("options".into(), options.into());
+        }
+    }
+    normalized.into()
+}
+
+/// Compares the registered commands with the new ones by type and name
+fn diff_application_commands(
+    existing: &[serde_json::Value],
+    new: &[serde_json::Value],
+) -> RegistrationSummary {
+    let existing = existing.iter().map(normalize_command).collect::<Vec<_>>();
+    let new = new.iter().map(normalize_command).collect::<Vec<_>>();
+    let same_command = |a: &serde_json::Value, b: &serde_json::Value| {
+        a["type"] == b["type"] && a["name"] == b["name"]
+    };
+
+    let mut summary = RegistrationSummary::default();
+    for new_command in &new {
+        match existing.iter().find(|x| same_command(x, new_command)) {
+            Some(existing_command) if existing_command == new_command => summary.unchanged += 1,
+            Some(_) => summary.updated += 1,
+            None => summary.created += 1,
+        }
+    }
+    summary.deleted = existing
+        .iter()
+        .filter(|existing_command| !new.iter().any(|x| same_command(x, existing_command)))
+        .count();
+    summary
+}
+
+/// Like [`register_in_guild`] or, if no guild is given, [`register_globally`], but first fetches
+/// the registered commands and only overwrites them if they differ from the given commands.
+///
+/// Avoids needless registrations on every startup, which count against rate limits.
+pub async fn register_if_changed<U, E>(
+    http: &serenity::Http,
+    commands: &[crate::Command<U, E>],
+    guild_id: Option<serenity::GuildId>,
+) -> Result<RegistrationSummary, RegistrationError> {
+    let commands_builder =
+        create_application_commands_filtered(commands, |command| match guild_id {
+            Some(guild_id) => command.is_available_in(Some(guild_id)),
+            None => command.slash_guilds().is_none(),
+        });
+    validate_payload(&commands_builder.0)?;
+
+    let existing_commands = match guild_id {
+        Some(guild_id) => http.get_guild_application_commands(guild_id.0).await?,
+        None => http.get_global_application_commands().await?,
+    };
+    let existing_commands = existing_commands
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| RegistrationError::Http(e.into()))?;
+
+    let summary = diff_application_commands(&existing_commands, &commands_builder.0);
+    if summary.changed() {
+        let json_value = serde_json::Value::Array(commands_builder.0);
+        match guild_id {
+            Some(guild_id) => {
+                http.create_guild_application_commands(guild_id.0, &json_value)
+                    .await?
+            }
+            None => http.create_global_application_commands(&json_value).await?,
+        };
+    }
+    Ok(summary)
+}
+
 /// Send a response to an interaction (slash command invocation).
 ///
 /// If a response to this interaction has already been sent, a
-/// [followup](serenity::model::interactions::Interaction::create_followup_message) is sent.
-pub async fn send_slash_reply<U, E>(
-    ctx: SlashContext<'_, U, E>,
+/// [followup](serenity::ApplicationCommandInteraction::create_followup_message) is sent.
+///
+/// The returned handle refers to the follow-up message or to the initial response, so that it
+/// can be fetched, edited or deleted later.
+pub async fn send_slash_reply<'ctx, U, E>(
+    ctx: SlashContext<'ctx, U, E>,
     builder: impl for<'a, 'b> FnOnce(&'a mut crate::CreateReply<'b>) -> &'a mut crate::CreateReply<'b>,
-) -> Result<(), serenity::Error> {
+) -> Result<crate::ReplyHandle<'ctx, U, E>, serenity::Error> {
     let mut reply = crate::CreateReply {
         ephemeral: ctx.command.options.ephemeral,
         ..Default::default()
     };
     builder(&mut reply);
+    if let Some(reply_callback) = ctx.framework.options().reply_callback {
+        reply_callback(crate::Context::Slash(ctx), &mut reply);
+    }
+    reply.apply_sanitization();
+    let ephemeral = reply.ephemeral;
+    let allowed_mentions = reply.allowed_mentions.clone();
+    let suppress_embeds = reply.suppress_embeds;
+    let additional_chunks = reply.split_off_content();
+
+    let handle = send_slash_reply_inner(ctx, reply).await?;
+    for chunk in additional_chunks {
+        let reply = crate::CreateReply {
+            content: Some(chunk),
+            allowed_mentions: allowed_mentions.clone(),
+            suppress_embeds,
+            ephemeral,
+            ..Default::default()
+        };
+        send_slash_reply_inner(ctx, reply).await?;
+    }
+    Ok(handle)
+}
+
+async fn send_slash_reply_inner<'ctx, U, E>(
+    ctx: SlashContext<'ctx, U, E>,
+    reply: crate::CreateReply<'_>,
+) -> Result<crate::ReplyHandle<'ctx, U, E>, serenity::Error> {
     let crate::CreateReply {
         content,
-        embed,
+        embeds,
         attachments,
+        components,
+        allowed_mentions,
+        reply: _,
+        reactions,
+        split: _,
+        sanitize_content: _,
+        suppress_embeds,
         ephemeral,
+        keep_existing_attachments: _,
+        reuse_response: _,
     } = reply;
+    let allowed_mentions =
+        allowed_mentions.or_else(|| ctx.framework.options().allowed_mentions.clone());
 
+    // The initial response can't carry files, so replies with attachments acknowledge the
+    // interaction first and send the files in a follow-up message, which replaces the
+    // "thinking..." state
+    if !attachments.is_empty() {
+        ctx.defer_with(ephemeral).await?;
+    }
+    // Claim the initial response before sending it, so that a concurrent auto-defer doesn't
+    // acknowledge the interaction a second time
     let has_sent_initial_response = ctx
         .has_sent_initial_response
-        .load(std::sync::atomic::Ordering::SeqCst);
+        .swap(true, std::sync::atomic::Ordering::SeqCst);
+
+    // Fill in the placeholder left by deferring. Editing the response can't add files, so replies
+    // with attachments go into a follow-up message instead
+    let fill_in_deferred_response = attachments.is_empty()
+        && ctx
+            .is_deferred
+            .swap(false, std::sync::atomic::Ordering::SeqCst);
 
-    if has_sent_initial_response {
+    let handle = if fill_in_deferred_response {
         ctx.interaction
-            .create_followup_message(ctx.discord, |f| {
+            .edit_original_interaction_response(ctx.discord, |f| {
                 if let Some(content) = content {
                     f.content(content);
                 }
-                if let Some(embed) = embed {
-                    f.add_embed(embed);
+                crate::replace_embeds(&mut f.0, embeds);
+                if let Some(components) = components {
+                    f.components(|f| {
+                        *f = components;
+                        f
+                    });
+                }
+                if let Some(allowed_mentions) = allowed_mentions {
+                    f.allowed_mentions(|m| {
+                        *m = allowed_mentions;
+                        m
+                    });
+                }
+                if suppress_embeds {
+                    crate::add_suppress_embeds_flag(&mut f.0);
                 }
+                f
+            })
+            .await?;
+        crate::ReplyHandle::slash_original(ctx)
+    } else if has_sent_initial_response {
+        let message = ctx
+            .interaction
+            .create_followup_message(ctx.discord, |f| {
+                if let Some(content) = content {
+                    f.content(content);
+                }
+                crate::replace_embeds(&mut f.0, embeds);
                 f.add_files(attachments);
+                if let Some(components) = components {
+                    f.components(|f| {
+                        *f = components;
+                        f
+                    });
+                }
+                if let Some(allowed_mentions) = allowed_mentions {
+                    f.allowed_mentions(|m| {
+                        *m = allowed_mentions;
+                        m
+                    });
+                }
+                if ephemeral {
+                    f.flags(serenity::InteractionApplicationCommandCallbackDataFlags::EPHEMERAL);
+                }
+                if suppress_embeds {
+                    crate::add_suppress_embeds_flag(&mut f.0);
+                }
                 f
             })
             .await?;
+        // A follow-up resolves a pending deferral, so later replies mustn't edit the original
+        // response anymore
+        ctx.is_deferred
+            .store(false, std::sync::atomic::Ordering::SeqCst);
+        crate::ReplyHandle::slash_followup(ctx, message)
     } else {
-        ctx.interaction
+        let result = ctx
+            .interaction
             .create_interaction_response(ctx.discord, |r| {
                 r.kind(serenity::InteractionResponseType::ChannelMessageWithSource)
                     .interaction_response_data(|r| {
                         if let Some(content) = content {
                             r.content(content);
                         }
-                        if let Some(embed) = embed {
-                            r.add_embed(embed);
+                        crate::replace_embeds(&mut r.0, embeds);
+                        if let Some(components) = components {
+                            r.components(|r| {
+                                *r = components;
+                                r
+                            });
                         }
-                        if let Some(allowed_mentions) = &ctx.framework.options().allowed_mentions {
+                        if let Some(allowed_mentions) = allowed_mentions {
                             r.allowed_mentions(|m| {
-                                *m = allowed_mentions.clone();
+                                *m = allowed_mentions;
                                 m
                             });
                         }
@@ -67,21 +703,280 @@ pub async fn send_slash_reply<U, E>(
                                 serenity::InteractionApplicationCommandCallbackDataFlags::EPHEMERAL,
                             );
                         }
+                        if suppress_embeds {
+                            crate::add_suppress_embeds_flag(&mut r.0);
+                        }
                         r
                     })
             })
-            .await?;
-        ctx.has_sent_initial_response
-            .store(true, std::sync::atomic::Ordering::SeqCst);
+            .await;
+        if let Err(e) = result {
+            ctx.has_sent_initial_response
+                .store(false, std::sync::atomic::Ordering::SeqCst);
+            return Err(e);
+        }
+        crate::ReplyHandle::slash_original(ctx)
+    };
+
+    if !reactions.is_empty() {
+        let message = handle.message().await?;
+        for reaction in reactions {
+            message.react(ctx.discord, reaction).await?;
+        }
     }
 
-    Ok(())
+    Ok(handle)
 }
 
 /// Shorthand of [`send_slash_reply`] with simple text content.
-pub async fn say_slash_reply<U, E>(
-    ctx: SlashContext<'_, U, E>,
-    text: String,
-) -> Result<(), serenity::Error> {
-    send_slash_reply(ctx, |m| m.content(text)).await
+pub async fn say_slash_reply<'ctx, U, E>(
+    ctx: SlashContext<'ctx, U, E>,
+    text: impl Into<String>,
+) -> Result<crate::ReplyHandle<'ctx, U, E>, serenity::Error> {
+    send_slash_reply(ctx, |m| m.content(text.into())).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_application_commands() {
+        let valid = serde_json::json!([{
+            "name": "config",
+            "description": "Change bot settings",
+            "options": [{ "name": "new_value", "description": "The new value" }],
+        }]);
+        assert!(validate_commands(valid.as_array().unwrap(), "").is_ok());
+
+        let context_menu = serde_json::json!([{ "name": "User Info", "type": 2 }]);
+        assert!(validate_commands(context_menu.as_array().unwrap(), "").is_ok());
+
+        let invalid_commands = [
+            serde_json::json!({ "name": "", "description": "Empty name" }),
+            serde_json::json!({ "name": "Config", "description": "Uppercase name" }),
+            serde_json::json!({ "name": "con fig", "description": "Name with space" }),
+            serde_json::json!({ "name": "config", "description": "" }),
+            serde_json::json!({ "name": "config", "description": "x".repeat(101) }),
+            serde_json::json!({
+                "name": "config",
+                "description": "Invalid option",
+                "options": [{ "name": "New Value", "description": "The new value" }],
+            }),
+            serde_json::json!({
+                "name": "config",
+                "description": "Optional option before required option",
+                "options": [
+                    { "type": 3, "name": "a", "description": "A" },
+                    { "type": 3, "name": "b", "description": "B", "required": true },
+                ],
+            }),
+            serde_json::json!({
+                "name": "config",
+                "description": "Too many choices",
+                "options": [{
+                    "type": 3,
+                    "name": "a",
+                    "description": "A",
+                    "choices": vec![serde_json::json!({ "name": "x", "value": "x" }); 26],
+                }],
+            }),
+            serde_json::json!({
+                "name": "config",
+                "description": "Too many options",
+                "options": (0..26)
+                    .map(|i| serde_json::json!({ "type": 3, "name": format!("o{}", i), "description": "O" }))
+                    .collect::<Vec<_>>(),
+            }),
+        ];
+        for command in &invalid_commands {
+            assert!(
+                validate_commands(std::slice::from_ref(command), "").is_err(),
+                "{} should be invalid",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_command_counts() {
+        let commands = |kind, count| vec![serde_json::json!({ "type": kind }); count];
+
+        assert!(validate_command_counts(&commands(1, 100)).is_ok());
+        assert!(validate_command_counts(&commands(2, 5)).is_ok());
+        assert!(validate_command_counts(&commands(2, 6)).is_err());
+
+        let mut mixed = commands(2, 5);
+        mixed.extend(commands(3, 5));
+        mixed.push(serde_json::json!({ "name": "untyped" }));
+        assert!(validate_command_counts(&mixed).is_ok());
+        mixed.extend(commands(3, 1));
+        assert!(matches!(
+            validate_command_counts(&mixed),
+            Err(RegistrationError::TooManyCommands { count: 6, .. })
+        ));
+    }
+
+    #[test]
+    fn test_diff_application_commands() {
+        let existing = serde_json::json!([
+            {
+                "id": "1",
+                "application_id": "2",
+                "type": 1,
+                "name": "volume",
+                "description": "Set the volume",
+                "options": [{
+                    "type": 4,
+                    "name": "level",
+                    "description": "New volume",
+                    "required": true,
+                    "min_value": 0.0,
+                    "max_value": 200.0,
+                }],
+            },
+            { "type": 1, "name": "ping", "description": "Pong", "options": [] },
+            { "type": 2, "name": "User Info", "description": "" },
+        ]);
+        let new = serde_json::json!([
+            {
+                "name": "volume",
+                "description": "Set the volume",
+                "options": [{
+                    "type": 4,
+                    "name": "level",
+                    "description": "New volume",
+                    "required": true,
+                    "min_value": 0,
+                    "max_value": 200,
+                }],
+            },
+            { "name": "ping", "description": "Pong!" },
+            { "name": "help", "description": "Show help" },
+        ]);
+
+        let summary =
+            diff_application_commands(existing.as_array().unwrap(), new.as_array().unwrap());
+        assert_eq!(
+            summary,
+            RegistrationSummary {
+                created: 1,
+                updated: 1,
+                deleted: 1,
+                unchanged: 1,
+            }
+        );
+        assert!(summary.changed());
+
+        let unchanged =
+            diff_application_commands(existing.as_array().unwrap(), existing.as_array().unwrap());
+        assert!(!unchanged.changed());
+
+        let ban = |permissions: &str| {
+            vec![serde_json::json!({
+                "name": "ban",
+                "description": "Ban a member",
+                "default_member_permissions": permissions,
+            })]
+        };
+        assert!(diff_application_commands(&ban("0"), &ban("4")).changed());
+        assert!(!diff_application_commands(&ban("4"), &ban("4")).changed());
+    }
+
+    #[test]
+    fn test_localize_commands() {
+        let mut commands = vec![crate::Command::<(), ()>::from(SlashCommand {
+            name: "ban",
+            description: "Ban a member",
+            action: SlashCommandAction::ChatInput(|_, _| Box::pin(async { Ok(()) })),
+            parameters: vec![SlashCommandParameter {
+                name: "reason",
+                builder: |o| {
+                    o.kind(serenity::ApplicationCommandOptionType::String)
+                        .name("reason")
+                        .description("Why")
+                        .add_string_choice("Spam", "spam")
+                },
+                autocomplete_callback: None,
+                name_localizations: Default::default(),
+                description_localizations: Default::default(),
+                choice_localizations: Default::default(),
+            }],
+            options: Default::default(),
+            name_localizations: vec![("de".to_owned(), "verbannen".to_owned())]
+                .into_iter()
+                .collect(),
+            description_localizations: Default::default(),
+        })];
+
+        let locales = ["de".to_owned(), "fr".to_owned()];
+        localize_commands(&mut commands, &locales, |locale, key| match (locale, key) {
+            ("de", "ban.name") => Some("bannen".into()),
+            ("fr", "ban.name") => Some("bannir".into()),
+            ("de", "ban.reason.description") => Some("Warum".into()),
+            ("fr", "ban.reason.choices.Spam") => Some("Pourriel".into()),
+            _ => None,
+        });
+
+        let payload = create_application_commands(&commands).0;
+        let command = &payload[0];
+        // Translations set on the command directly take precedence
+        assert_eq!(
+            command["name_localizations"],
+            serde_json::json!({ "de": "verbannen", "fr": "bannir" })
+        );
+        assert!(command.get("description_localizations").is_none());
+
+        let option = &command["options"][0];
+        assert!(option.get("name_localizations").is_none());
+        assert_eq!(
+            option["description_localizations"],
+            serde_json::json!({ "de": "Warum" })
+        );
+        assert_eq!(
+            option["choices"][0]["name_localizations"],
+            serde_json::json!({ "fr": "Pourriel" })
+        );
+    }
+
+    #[test]
+    fn test_guild_specific_commands() {
+        let command = |name, guilds| {
+            crate::Command::<(), ()>::from(SlashCommand {
+                name,
+                description: "Description",
+                action: SlashCommandAction::ChatInput(|_, _| Box::pin(async { Ok(()) })),
+                parameters: Vec::new(),
+                options: SlashCommandOptions {
+                    guilds,
+                    ..Default::default()
+                },
+                name_localizations: Default::default(),
+                description_localizations: Default::default(),
+            })
+        };
+        let commands = vec![
+            command("ping", None),
+            command("premium", Some(vec![serenity::GuildId(1)])),
+        ];
+        let names = |filter: &dyn Fn(&crate::Command<(), ()>) -> bool| {
+            create_application_commands_filtered(&commands, filter)
+                .0
+                .iter()
+                .map(|command| command["name"].as_str().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(&|_| true), ["ping", "premium"]);
+        assert_eq!(names(&|cmd| cmd.slash_guilds().is_none()), ["ping"]);
+        assert_eq!(
+            names(&|cmd| cmd.is_available_in(Some(serenity::GuildId(1)))),
+            ["ping", "premium"]
+        );
+        assert_eq!(
+            names(&|cmd| cmd.is_available_in(Some(serenity::GuildId(2)))),
+            ["ping"]
+        );
+        assert!(!commands[1].is_available_in(None));
+    }
 }
@@ -0,0 +1,160 @@
+//! Modals, i.e. popup forms with text inputs that can be sent in response to interactions.
+
+use crate::serenity_prelude as serenity;
+
+/// A modal with a fixed set of text inputs, shown via [`crate::SlashContext::send_modal`].
+/// Derive it with `#[derive(poise::Modal)]` on a struct with `String` and `Option<String>` fields:
+///
+/// ```rust,ignore
+/// #[derive(poise::Modal)]
+/// #[name = "Report a bug"] // Modal title, defaults to the struct name
+/// struct BugReport {
+///     #[name = "Summary"] // Input label, defaults to the field name
+///     #[max_length = 100]
+///     summary: String,
+///     #[paragraph] // Multi-line input instead of a single line
+///     #[placeholder = "What did you do before the bug happened?"]
+///     steps_to_reproduce: Option<String>, // Optional inputs are wrapped in Option
+/// }
+/// ```
+pub trait Modal: Sized {
+    /// Title shown at the top of the modal
+    fn title() -> &'static str;
+
+    /// Adds the text inputs of this modal, one action row per input
+    fn create_fields(builder: &mut serenity::CreateComponents) -> &mut serenity::CreateComponents;
+
+    /// Parses the submitted text inputs
+    fn parse(data: &serenity::ModalSubmitInteractionData) -> Result<Self, ModalParseError>;
+}
+
+/// Emitted when the submitted data of a modal doesn't fit the [`Modal`] definition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModalParseError {
+    /// A required text input was missing or empty
+    MissingField(&'static str),
+    /// A text input was longer than its maximum length
+    TooLong {
+        field: &'static str,
+        max_length: usize,
+    },
+}
+
+impl std::fmt::Display for ModalParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingField(field) => write!(f, "missing required field `{}`", field),
+            Self::TooLong { field, max_length } => write!(
+                f,
+                "field `{}` is longer than {} characters",
+                field, max_length
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ModalParseError {}
+
+/// Returned from [`crate::SlashContext::send_modal`]
+#[derive(Debug)]
+pub enum ModalError {
+    /// The modal couldn't be sent or the submission couldn't be acknowledged
+    Serenity(serenity::Error),
+    /// The submitted data couldn't be parsed
+    Parse(ModalParseError),
+}
+
+impl std::fmt::Display for ModalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Serenity(e) => e.fmt(f),
+            Self::Parse(e) => write!(f, "invalid modal submission: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ModalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Serenity(e) => Some(e),
+            Self::Parse(e) => Some(e),
+        }
+    }
+}
+
+impl From<serenity::Error> for ModalError {
+    fn from(e: serenity::Error) -> Self {
+        Self::Serenity(e)
+    }
+}
+
+impl From<ModalParseError> for ModalError {
+    fn from(e: ModalParseError) -> Self {
+        Self::Parse(e)
+    }
+}
+
+/// Finds the value of the text input with the given custom ID in a modal submission. Empty
+/// inputs are treated like missing ones. Used by the [`Modal`] derive macro.
+#[doc(hidden)]
+pub fn parse_modal_field(
+    data: &serenity::ModalSubmitInteractionData,
+    custom_id: &'static str,
+    max_length: Option<usize>,
+) -> Result<Option<String>, ModalParseError> {
+    let value = data
+        .components
+        .iter()
+        .flat_map(|row| &row.components)
+        .find_map(|component| match component {
+            serenity::ActionRowComponent::InputText(input) if input.custom_id == custom_id => {
+                Some(&input.value)
+            }
+            _ => None,
+        });
+
+    let value = match value {
+        Some(x) if !x.is_empty() => x,
+        _ => return Ok(None),
+    };
+    if let Some(max_length) = max_length {
+        if value.chars().count() > max_length {
+            return Err(ModalParseError::TooLong {
+                field: custom_id,
+                max_length,
+            });
+        }
+    }
+    Ok(Some(value.clone()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_modal_field() {
+        let data: serenity::ModalSubmitInteractionData = serde_json::from_value(serde_json::json!({
+            "custom_id": "bug_report",
+            "components": [
+                { "type": 1, "components": [{ "type": 4, "custom_id": "summary", "value": "crash" }] },
+                { "type": 1, "components": [{ "type": 4, "custom_id": "steps", "value": "" }] },
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            parse_modal_field(&data, "summary", None),
+            Ok(Some("crash".to_owned()))
+        );
+        assert_eq!(parse_modal_field(&data, "steps", None), Ok(None));
+        assert_eq!(parse_modal_field(&data, "missing", None), Ok(None));
+        assert_eq!(
+            parse_modal_field(&data, "summary", Some(3)),
+            Err(ModalParseError::TooLong {
+                field: "summary",
+                max_length: 3
+            })
+        );
+    }
+}
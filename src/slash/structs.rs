@@ -7,6 +7,8 @@ pub struct SlashContext<'a, U, E> {
     pub discord: &'a serenity::Context,
     pub interaction: &'a serenity::ApplicationCommandInteraction,
     pub has_sent_initial_response: &'a std::sync::atomic::AtomicBool,
+    /// Set while the initial response is a deferred placeholder that no reply has filled in yet
+    pub is_deferred: &'a std::sync::atomic::AtomicBool,
     pub framework: &'a Framework<U, E>,
     pub command: &'a SlashCommand<U, E>,
     pub data: &'a U,
@@ -23,18 +25,274 @@ impl<U, E> crate::_GetGenerics for SlashContext<'_, U, E> {
 }
 
 impl<U, E> SlashContext<'_, U, E> {
-    pub async fn defer_response(&self) -> Result<(), serenity::Error> {
+    /// Returns the guild member of the invoking user, as sent along with the interaction. Returns
+    /// None outside of guilds.
+    ///
+    /// Async only for consistency with [`crate::PrefixContext::author_member`].
+    pub async fn author_member(&self) -> Option<std::borrow::Cow<'_, serenity::Member>> {
         self.interaction
+            .member
+            .as_ref()
+            .map(std::borrow::Cow::Borrowed)
+    }
+
+    /// Returns the full name of the invoked command, including subcommand groups and
+    /// subcommands, e.g. `config prefix set`
+    pub fn qualified_command_name(&self) -> String {
+        let mut name = self.interaction.data.name.clone();
+        let mut options = &self.interaction.data.options;
+        while let [option] = options.as_slice() {
+            match option.kind {
+                serenity::ApplicationCommandOptionType::SubCommand
+                | serenity::ApplicationCommandOptionType::SubCommandGroup => {
+                    name.push(' ');
+                    name.push_str(&option.name);
+                    options = &option.options;
+                }
+                _ => break,
+            }
+        }
+        name
+    }
+
+    /// Shows the given modal to the user and waits for its submission.
+    ///
+    /// The modal must be the initial response to the interaction, so this must be called before
+    /// any reply or defer. Afterwards, replies are sent as followup messages. Returns `None` if the
+    /// user doesn't submit the modal within the given timeout, e.g. because they closed it.
+    pub async fn send_modal<M: crate::Modal>(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<Option<M>, crate::ModalError> {
+        let custom_id = self.interaction.id.0.to_string();
+
+        // Register before sending, so that a fast submission can't slip through
+        let receiver = self.framework.await_modal_submit(custom_id.clone());
+        self.has_sent_initial_response
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        let result = self
+            .interaction
+            .create_interaction_response(self.discord, |f| {
+                f.kind(serenity::InteractionResponseType::Modal)
+                    .interaction_response_data(|f| {
+                        f.custom_id(&custom_id)
+                            .title(M::title())
+                            .components(M::create_fields)
+                    })
+            })
+            .await;
+        if let Err(e) = result {
+            self.has_sent_initial_response
+                .store(false, std::sync::atomic::Ordering::SeqCst);
+            self.framework.cancel_modal_submit(&custom_id);
+            return Err(e.into());
+        }
+
+        let submission = match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(x)) => x,
+            _ => {
+                self.framework.cancel_modal_submit(&custom_id);
+                return Ok(None);
+            }
+        };
+
+        // The submission is an interaction of its own that must be acknowledged
+        submission
             .create_interaction_response(self.discord, |f| {
-                f.kind(serenity::InteractionResponseType::DeferredChannelMessageWithSource)
+                f.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
             })
             .await?;
-        self.has_sent_initial_response
+
+        Ok(Some(M::parse(&submission.data)?))
+    }
+
+    /// The locale of the invoking user, e.g. `de` or `en-US`, for matching responses to the
+    /// language of the localized command
+    pub fn locale(&self) -> &str {
+        &self.interaction.locale
+    }
+
+    /// The preferred locale of the guild the command was invoked in, if any
+    pub fn guild_locale(&self) -> Option<&str> {
+        self.interaction.guild_locale.as_deref()
+    }
+
+    /// Acknowledges the interaction, which shows a loading state to the user and gives the
+    /// command 15 minutes instead of 3 seconds to respond. The first reply afterwards fills in the
+    /// deferred response.
+    ///
+    /// Does nothing if an initial response has already been sent.
+    pub async fn defer(&self) -> Result<(), serenity::Error> {
+        self.defer_with(false).await
+    }
+
+    /// Like [`Self::defer`], but the eventual response will be ephemeral. Discord fixes the
+    /// ephemeral flag at defer time, so it can't be changed by the reply.
+    pub async fn defer_ephemeral(&self) -> Result<(), serenity::Error> {
+        self.defer_with(true).await
+    }
+
+    /// Alias of [`Self::defer`]
+    pub async fn defer_response(&self) -> Result<(), serenity::Error> {
+        self.defer().await
+    }
+
+    pub(crate) async fn defer_with(&self, ephemeral: bool) -> Result<(), serenity::Error> {
+        // Claim the initial response before sending it, so that a concurrent reply doesn't
+        // acknowledge the interaction a second time
+        if self
+            .has_sent_initial_response
+            .swap(true, std::sync::atomic::Ordering::SeqCst)
+        {
+            return Ok(());
+        }
+
+        let result = self
+            .interaction
+            .create_interaction_response(self.discord, |f| {
+                f.kind(serenity::InteractionResponseType::DeferredChannelMessageWithSource);
+                if ephemeral {
+                    f.interaction_response_data(|d| {
+                        d.flags(serenity::InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    });
+                }
+                f
+            })
+            .await;
+        if let Err(e) = result {
+            self.has_sent_initial_response
+                .store(false, std::sync::atomic::Ordering::SeqCst);
+            return Err(e);
+        }
+        self.is_deferred
             .store(true, std::sync::atomic::Ordering::SeqCst);
         Ok(())
     }
 }
 
+/// Passed to autocomplete callbacks of slash command parameters
+#[non_exhaustive]
+pub struct AutocompleteContext<'a, U, E> {
+    pub discord: &'a serenity::Context,
+    pub interaction: &'a serenity::AutocompleteInteraction,
+    pub framework: &'a Framework<U, E>,
+    pub command: &'a SlashCommand<U, E>,
+    pub data: &'a U,
+}
+impl<U, E> Clone for AutocompleteContext<'_, U, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<U, E> Copy for AutocompleteContext<'_, U, E> {}
+
+/// Passed to component handlers registered in [`crate::FrameworkOptions::component_handlers`]
+#[non_exhaustive]
+pub struct ComponentContext<'a, U, E> {
+    pub discord: &'a serenity::Context,
+    pub interaction: &'a serenity::MessageComponentInteraction,
+    pub framework: &'a Framework<U, E>,
+    /// The handler whose custom ID pattern matched this interaction
+    pub handler: &'a ComponentHandler<U, E>,
+    pub data: &'a U,
+}
+impl<U, E> Clone for ComponentContext<'_, U, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<U, E> Copy for ComponentContext<'_, U, E> {}
+
+impl<U, E> ComponentContext<'_, U, E> {
+    /// Custom ID of the clicked component
+    pub fn custom_id(&self) -> &str {
+        &self.interaction.data.custom_id
+    }
+
+    /// For prefix patterns like `vote:*`, the part of the custom ID after the prefix, e.g. `42`
+    /// for `vote:42`. For exact patterns, an empty string.
+    pub fn custom_id_suffix(&self) -> &str {
+        self.handler.match_custom_id(self.custom_id()).unwrap_or("")
+    }
+
+    /// Values of the options selected in a select menu. Empty for other component types.
+    pub fn values(&self) -> &[String] {
+        &self.interaction.data.values
+    }
+
+    /// Maps the selected values of a select menu back into choices. The select menu options must
+    /// have been created with [`crate::SlashChoiceParameter::create_select_options`].
+    pub fn selected_choices<T: crate::SlashChoiceParameter>(
+        &self,
+    ) -> Result<Vec<T>, crate::InvalidChoice> {
+        self.values()
+            .iter()
+            .map(|value| T::from_component_value(value).ok_or(crate::InvalidChoice))
+            .collect()
+    }
+
+    /// Responds to the interaction by editing the message that contains the component
+    pub async fn update_message(
+        &self,
+        builder: impl FnOnce(
+            &mut serenity::CreateInteractionResponseData,
+        ) -> &mut serenity::CreateInteractionResponseData,
+    ) -> Result<(), serenity::Error> {
+        self.interaction
+            .create_interaction_response(self.discord, |f| {
+                f.kind(serenity::InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(builder)
+            })
+            .await
+    }
+}
+
+/// Handles message component interactions (e.g. button clicks) whose custom ID matches a pattern
+pub struct ComponentHandler<U, E> {
+    /// Custom ID to match. If it ends with `*`, it's matched as a prefix, e.g. `vote:*` matches
+    /// `vote:1` and `vote:2`. Otherwise, the custom ID must match exactly.
+    pub custom_id: &'static str,
+    /// Called when a matching component interaction is received. Errors are passed to
+    /// [`crate::FrameworkOptions::on_error`] with [`crate::ErrorContext::Component`].
+    pub action: for<'a> fn(ComponentContext<'a, U, E>) -> BoxFuture<'a, Result<(), E>>,
+}
+
+impl<U, E> ComponentHandler<U, E> {
+    /// If the given custom ID matches this handler's pattern, returns the part after the prefix
+    /// (empty for exact matches)
+    pub fn match_custom_id<'b>(&self, custom_id: &'b str) -> Option<&'b str> {
+        match self.custom_id.strip_suffix('*') {
+            Some(prefix) => custom_id.strip_prefix(prefix),
+            None if self.custom_id == custom_id => Some(""),
+            None => None,
+        }
+    }
+}
+
+/// A single suggestion returned from an autocomplete callback
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutocompleteChoice {
+    /// Text shown to the user
+    pub name: String,
+    /// Value that is filled in when the user picks this choice. Must match the parameter type
+    pub value: serde_json::Value,
+}
+
+impl From<String> for AutocompleteChoice {
+    fn from(name: String) -> Self {
+        Self {
+            value: name.clone().into(),
+            name,
+        }
+    }
+}
+
+impl From<&str> for AutocompleteChoice {
+    fn from(name: &str) -> Self {
+        name.to_owned().into()
+    }
+}
+
 pub struct SlashCommandErrorContext<'a, U, E> {
     pub while_checking: bool,
     pub command: &'a SlashCommand<U, E>,
@@ -51,69 +309,388 @@ impl<U, E> Clone for SlashCommandErrorContext<'_, U, E> {
     }
 }
 
+/// Context of an error in the autocomplete callback of a slash command parameter
+pub struct AutocompleteErrorContext<'a, U, E> {
+    pub ctx: AutocompleteContext<'a, U, E>,
+    /// Name of the parameter that was being autocompleted
+    pub parameter_name: &'static str,
+    /// What the user had typed so far
+    pub partial_input: String,
+}
+
+impl<U, E> Clone for AutocompleteErrorContext<'_, U, E> {
+    fn clone(&self) -> Self {
+        Self {
+            ctx: self.ctx,
+            parameter_name: self.parameter_name,
+            partial_input: self.partial_input.clone(),
+        }
+    }
+}
+
 pub struct SlashCommandOptions<U, E> {
     /// Falls back to the framework-specified value on None. See there for documentation.
-    pub on_error: Option<fn(E, SlashCommandErrorContext<'_, U, E>) -> BoxFuture<'_, ()>>,
+    pub on_error: Option<
+        fn(crate::FrameworkError<E>, SlashCommandErrorContext<'_, U, E>) -> BoxFuture<'_, ()>,
+    >,
+    /// Called instead of [`crate::FrameworkOptions::on_error`] for errors in the autocomplete
+    /// callbacks of this command's parameters. Either way, an empty suggestion list is sent.
+    /// Failures to send the suggestions still go to [`crate::FrameworkOptions::on_error`].
+    pub on_autocomplete_error:
+        Option<fn(E, AutocompleteErrorContext<'_, U, E>) -> BoxFuture<'_, ()>>,
     /// If this function returns false, this command will not be executed.
     pub check: Option<fn(SlashContext<'_, U, E>) -> BoxFuture<'_, Result<bool, E>>>,
     /// Falls back to the framework-specified value on None. See there for documentation.
     pub defer_response: Option<bool>,
+    /// The slash command equivalent of [`crate::PrefixCommandOptions::broadcast_typing`]: the
+    /// response is deferred, showing "Bot is thinking...", if the command hasn't responded
+    /// within the given delay.
+    pub broadcast_typing: Option<crate::BroadcastTypingBehavior>,
     /// Whether responses to this command should be ephemeral by default.
     pub ephemeral: bool,
     /// Permissions which a user needs to have so that the slash command runs.
+    ///
+    /// On top-level commands, this is also registered as the command's
+    /// `default_member_permissions`, so that Discord hides the command from users who lack them.
     pub required_permissions: serenity::Permissions,
+    /// Overrides the `default_member_permissions` registered for this command, for when the
+    /// permissions under which the command is visible should differ from
+    /// [`Self::required_permissions`]. Only has an effect on top-level commands.
+    pub default_member_permissions: Option<serenity::Permissions>,
     /// If true, only users from the [owners list](crate::FrameworkOptions::owners) may use this
     /// command.
     pub owners_only: bool,
+    /// If true, this command can only be used in guilds. On top-level commands, this is also
+    /// registered as `dm_permission: false`, so that Discord doesn't offer the command in DMs.
+    ///
+    /// Invocations outside of guilds are passed to
+    /// [`SlashFrameworkOptions::guild_only_handler`] instead of running the command.
+    pub guild_only: bool,
+    /// Cooldowns of this command. They are shared with the prefix implementation of the same
+    /// name.
+    pub cooldowns: crate::CooldownConfig,
+    /// If set, this command is only registered in the given guilds, for example for premium
+    /// commands. Only applies to top-level commands.
+    ///
+    /// [`crate::register_globally`] skips such commands and [`crate::register_in_guild`] only
+    /// includes them in the listed guilds; use [`crate::register_guild_specific`] to register
+    /// them in their guilds. Interactions are accepted regardless, because Discord only sends
+    /// them for registered commands.
+    pub guilds: Option<Vec<serenity::GuildId>>,
+    /// If true, this command is only invocable as a prefix command. It's skipped when registering
+    /// slash commands and interactions for it are ignored.
+    pub prefix_only: bool,
 }
 
 impl<U, E> Default for SlashCommandOptions<U, E> {
     fn default() -> Self {
         Self {
             on_error: None,
+            on_autocomplete_error: None,
             check: None,
             defer_response: None,
+            broadcast_typing: None,
             ephemeral: false,
             required_permissions: serenity::Permissions::empty(),
+            default_member_permissions: None,
             owners_only: false,
+            guild_only: false,
+            cooldowns: Default::default(),
+            guilds: None,
+            prefix_only: false,
+        }
+    }
+}
+
+/// A parameter of a slash command
+pub struct SlashCommandParameter<U, E> {
+    pub name: &'static str,
+    /// Fills in the parameter type, name, description and so on
+    pub builder: fn(
+        &mut serenity::CreateApplicationCommandOption,
+    ) -> &mut serenity::CreateApplicationCommandOption,
+    /// If set, Discord asks for suggestions while the user is typing this parameter. The callback
+    /// receives the partial input and returns up to 25 choices; further choices are discarded.
+    pub autocomplete_callback: Option<
+        for<'a> fn(
+            AutocompleteContext<'a, U, E>,
+            &'a str,
+        ) -> BoxFuture<'a, Result<Vec<AutocompleteChoice>, E>>,
+    >,
+    /// Translations of the parameter name, keyed by locale
+    pub name_localizations: std::collections::HashMap<String, String>,
+    /// Translations of the parameter description, keyed by locale
+    pub description_localizations: std::collections::HashMap<String, String>,
+    /// Translations of the choice names, keyed by the untranslated choice name and then by locale
+    pub choice_localizations:
+        std::collections::HashMap<String, std::collections::HashMap<String, String>>,
+}
+
+/// Adds a localization map to a builder, unless it's empty
+fn insert_localizations(
+    builder: &mut std::collections::HashMap<&'static str, serde_json::Value>,
+    key: &'static str,
+    localizations: &std::collections::HashMap<String, String>,
+) {
+    if !localizations.is_empty() {
+        builder.insert(key, serde_json::json!(localizations));
+    }
+}
+
+impl<U, E> SlashCommandParameter<U, E> {
+    pub fn create<'a>(
+        &self,
+        option: &'a mut serenity::CreateApplicationCommandOption,
+    ) -> &'a mut serenity::CreateApplicationCommandOption {
+        (self.builder)(option);
+        if self.autocomplete_callback.is_some() {
+            option.set_autocomplete(true);
+        }
+
+        insert_localizations(
+            &mut option.0,
+            "name_localizations",
+            &self.name_localizations,
+        );
+        insert_localizations(
+            &mut option.0,
+            "description_localizations",
+            &self.description_localizations,
+        );
+        if let Some(serde_json::Value::Array(choices)) = option.0.get_mut("choices") {
+            for choice in choices {
+                let localizations = choice["name"]
+                    .as_str()
+                    .and_then(|name| self.choice_localizations.get(name));
+                if let Some(localizations) = localizations {
+                    choice["name_localizations"] = serde_json::json!(localizations);
+                }
+            }
         }
+        option
     }
 }
 
+/// The function that runs when an application command is invoked. The variant also determines
+/// the kind of application command.
+pub enum SlashCommandAction<U, E> {
+    /// A regular slash command, invoked with its arguments
+    ChatInput(
+        for<'a> fn(
+            SlashContext<'a, U, E>,
+            &'a [serenity::ApplicationCommandInteractionDataOption],
+        ) -> BoxFuture<'a, Result<(), crate::FrameworkError<E>>>,
+    ),
+    /// A context menu command on users, invoked with the targeted user
+    User(
+        for<'a> fn(
+            SlashContext<'a, U, E>,
+            serenity::User,
+        ) -> BoxFuture<'a, Result<(), crate::FrameworkError<E>>>,
+    ),
+    /// A context menu command on messages, invoked with the targeted message
+    Message(
+        for<'a> fn(
+            SlashContext<'a, U, E>,
+            serenity::Message,
+        ) -> BoxFuture<'a, Result<(), crate::FrameworkError<E>>>,
+    ),
+}
+
+impl<U, E> SlashCommandAction<U, E> {
+    /// Whether an interaction of the given kind is meant for a command with this action
+    pub fn matches_kind(&self, kind: serenity::ApplicationCommandType) -> bool {
+        match self {
+            Self::ChatInput(_) => kind == serenity::ApplicationCommandType::ChatInput,
+            Self::User(_) => kind == serenity::ApplicationCommandType::User,
+            Self::Message(_) => kind == serenity::ApplicationCommandType::Message,
+        }
+    }
+}
+
+/// Implemented for the types that context menu commands can target. Used by the
+/// `#[poise::command(context_menu_command = "...")]` macro to pick the kind of context menu
+/// command based on the parameter type.
+pub trait ContextMenuCommandTarget<U, E>: Sized {
+    fn into_action(
+        action: for<'a> fn(
+            SlashContext<'a, U, E>,
+            Self,
+        ) -> BoxFuture<'a, Result<(), crate::FrameworkError<E>>>,
+    ) -> SlashCommandAction<U, E>;
+}
+
+impl<U, E> ContextMenuCommandTarget<U, E> for serenity::User {
+    fn into_action(
+        action: for<'a> fn(
+            SlashContext<'a, U, E>,
+            Self,
+        ) -> BoxFuture<'a, Result<(), crate::FrameworkError<E>>>,
+    ) -> SlashCommandAction<U, E> {
+        SlashCommandAction::User(action)
+    }
+}
+
+impl<U, E> ContextMenuCommandTarget<U, E> for serenity::Message {
+    fn into_action(
+        action: for<'a> fn(
+            SlashContext<'a, U, E>,
+            Self,
+        ) -> BoxFuture<'a, Result<(), crate::FrameworkError<E>>>,
+    ) -> SlashCommandAction<U, E> {
+        SlashCommandAction::Message(action)
+    }
+}
+
+/// A slash command or context menu command.
+///
+/// Context menu commands have no description, parameters or subcommands; those fields are
+/// ignored for them.
 pub struct SlashCommand<U, E> {
     pub name: &'static str,
     pub description: &'static str,
-    pub action: for<'a> fn(
-        SlashContext<'a, U, E>,
-        &'a [serenity::ApplicationCommandInteractionDataOption],
-    ) -> BoxFuture<'a, Result<(), E>>,
-    pub parameters: Vec<
-        fn(
-            &mut serenity::CreateApplicationCommandOption,
-        ) -> &mut serenity::CreateApplicationCommandOption,
-    >,
+    pub action: SlashCommandAction<U, E>,
+    pub parameters: Vec<SlashCommandParameter<U, E>>,
     pub options: SlashCommandOptions<U, E>,
+    /// Translations of the command name, keyed by locale
+    pub name_localizations: std::collections::HashMap<String, String>,
+    /// Translations of the command description, keyed by locale
+    pub description_localizations: std::collections::HashMap<String, String>,
+}
+
+/// Yields the slash implementations of the given commands, each with the command's subcommands.
+/// Commands without slash implementation and [prefix only](SlashCommandOptions::prefix_only)
+/// commands are skipped.
+pub(crate) fn slash_commands<'a, U, E>(
+    commands: &'a [crate::Command<U, E>],
+) -> impl Iterator<Item = (&'a SlashCommand<U, E>, &'a [crate::Command<U, E>])> {
+    commands.iter().filter_map(|command| {
+        let slash_command = command
+            .slash_action
+            .as_ref()
+            .filter(|slash_command| !slash_command.options.prefix_only)?;
+        Some((slash_command, command.subcommands.as_slice()))
+    })
 }
 
 impl<U, E> SlashCommand<U, E> {
+    /// Fills in the registration payload of this command. If any of the given subcommands has a
+    /// slash implementation, this command itself can't be invoked and its parameters are ignored.
+    /// Subcommands may themselves have subcommands, which turns them into subcommand groups;
+    /// Discord doesn't allow nesting any deeper.
     pub fn create<'a>(
         &self,
+        subcommands: &[crate::Command<U, E>],
         interaction: &'a mut serenity::CreateApplicationCommand,
     ) -> &'a mut serenity::CreateApplicationCommand {
         interaction.name(self.name);
+        insert_localizations(
+            &mut interaction.0,
+            "name_localizations",
+            &self.name_localizations,
+        );
+        let permissions = self
+            .options
+            .default_member_permissions
+            .unwrap_or(self.options.required_permissions);
+        if !permissions.is_empty() {
+            interaction.0.insert(
+                "default_member_permissions",
+                serde_json::Value::String(permissions.bits().to_string()),
+            );
+        }
+        if self.options.guild_only {
+            interaction
+                .0
+                .insert("dm_permission", serde_json::Value::Bool(false));
+        }
+
+        // Discord doesn't allow descriptions or options on context menu commands
+        match self.action {
+            SlashCommandAction::ChatInput(_) => {}
+            SlashCommandAction::User(_) => {
+                return interaction.kind(serenity::ApplicationCommandType::User)
+            }
+            SlashCommandAction::Message(_) => {
+                return interaction.kind(serenity::ApplicationCommandType::Message)
+            }
+        }
+
         interaction.description(self.description);
-        for create_option in &self.parameters {
-            let mut option = serenity::CreateApplicationCommandOption::default();
-            create_option(&mut option);
-            interaction.add_option(option);
+        insert_localizations(
+            &mut interaction.0,
+            "description_localizations",
+            &self.description_localizations,
+        );
+        let mut subcommands = slash_commands(subcommands).peekable();
+        if subcommands.peek().is_none() {
+            for parameter in &self.parameters {
+                let mut option = serenity::CreateApplicationCommandOption::default();
+                parameter.create(&mut option);
+                interaction.add_option(option);
+            }
+        } else {
+            for (subcommand, subsubcommands) in subcommands {
+                let mut option = serenity::CreateApplicationCommandOption::default();
+                subcommand.create_as_subcommand(subsubcommands, &mut option);
+                interaction.add_option(option);
+            }
         }
         interaction
     }
+
+    /// Like [`Self::create`], but for use as a subcommand or subcommand group of another command
+    pub fn create_as_subcommand<'a>(
+        &self,
+        subcommands: &[crate::Command<U, E>],
+        option: &'a mut serenity::CreateApplicationCommandOption,
+    ) -> &'a mut serenity::CreateApplicationCommandOption {
+        option.name(self.name);
+        option.description(self.description);
+        insert_localizations(
+            &mut option.0,
+            "name_localizations",
+            &self.name_localizations,
+        );
+        insert_localizations(
+            &mut option.0,
+            "description_localizations",
+            &self.description_localizations,
+        );
+        let mut subcommands = slash_commands(subcommands).peekable();
+        if subcommands.peek().is_none() {
+            option.kind(serenity::ApplicationCommandOptionType::SubCommand);
+            for parameter in &self.parameters {
+                let mut sub_option = serenity::CreateApplicationCommandOption::default();
+                parameter.create(&mut sub_option);
+                option.add_sub_option(sub_option);
+            }
+        } else {
+            option.kind(serenity::ApplicationCommandOptionType::SubCommandGroup);
+            for (subcommand, subsubcommands) in subcommands {
+                let mut sub_option = serenity::CreateApplicationCommandOption::default();
+                subcommand.create_as_subcommand(subsubcommands, &mut sub_option);
+                option.add_sub_option(sub_option);
+            }
+        }
+        option
+    }
 }
 
 pub struct SlashFrameworkOptions<U, E> {
-    /// List of bot commands.
-    pub commands: Vec<SlashCommand<U, E>>,
+    /// Locales to translate the commands into with [`Self::localizer`], e.g. `de` or `fr`.
+    pub locales: Vec<String>,
+    /// Translates command names and descriptions, parameter names and descriptions, and choice
+    /// names. Called with a locale from [`Self::locales`] and a key, and returns the translation,
+    /// if any. Keys look like this:
+    /// - `ban.name` and `ban.description` for the command `/ban`
+    /// - `config.prefix.name` for the subcommand `/config prefix`
+    /// - `ban.reason.name` and `ban.reason.description` for the parameter `reason` of `/ban`
+    /// - `ban.reason.choices.Spam` for the choice `Spam` of that parameter
+    ///
+    /// The translations are looked up once when the framework is created and fill in the
+    /// localization fields of the commands, without overwriting translations set there directly.
+    pub localizer: Option<fn(&str, &str) -> Option<String>>,
     /// Provide a callback to be invoked before every command. The command will only be executed
     /// if the callback returns true.
     ///
@@ -136,19 +713,60 @@ pub struct SlashFrameworkOptions<U, E> {
     /// This handler should be used to reply with some form of error message. If this handler does
     /// nothing, the user will be shown "Interaction failed" by their Discord client.
     pub missing_permissions_handler: fn(SlashContext<'_, U, E>) -> BoxFuture<'_, ()>,
+    /// Invoked when a [guild-only](SlashCommandOptions::guild_only) command is used outside of a
+    /// guild. The default handler replies ephemerally.
+    pub guild_only_handler: fn(SlashContext<'_, U, E>) -> BoxFuture<'_, ()>,
+    /// Invoked for application command interactions that don't match any command, for example
+    /// because of a stale registration of a removed or renamed command.
+    ///
+    /// This handler should respond to the interaction, because Discord shows "Interaction failed"
+    /// otherwise. The default handler logs a warning and replies ephemerally.
+    pub unknown_command_handler: for<'a> fn(
+        &'a serenity::Context,
+        &'a serenity::ApplicationCommandInteraction,
+    ) -> BoxFuture<'a, ()>,
 }
 
 impl<U: Send + Sync, E> Default for SlashFrameworkOptions<U, E> {
     fn default() -> Self {
         Self {
-            commands: Vec::new(),
+            locales: Vec::new(),
+            localizer: None,
             command_check: |_| Box::pin(async { Ok(true) }),
             defer_response: false,
             missing_permissions_handler: |ctx| {
                 Box::pin(async move {
                     let response = format!(
                         "You don't have the required permissions for `/{}`",
-                        ctx.command.name
+                        ctx.qualified_command_name()
+                    );
+                    let _: Result<_, _> =
+                        crate::send_slash_reply(ctx, |f| f.content(response).ephemeral(true)).await;
+                })
+            },
+            unknown_command_handler: |ctx, interaction| {
+                Box::pin(async move {
+                    println!(
+                        "Warning: received unknown interaction \"{}\"",
+                        interaction.data.name
+                    );
+                    let _: Result<_, _> = interaction
+                        .create_interaction_response(ctx, |r| {
+                            r.kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+                                .interaction_response_data(|d| {
+                                    d.content("This command doesn't exist anymore").flags(
+                                        serenity::InteractionApplicationCommandCallbackDataFlags::EPHEMERAL,
+                                    )
+                                })
+                        })
+                        .await;
+                })
+            },
+            guild_only_handler: |ctx| {
+                Box::pin(async move {
+                    let response = format!(
+                        "`/{}` can only be used in servers",
+                        ctx.qualified_command_name()
                     );
                     let _: Result<_, _> =
                         crate::send_slash_reply(ctx, |f| f.content(response).ephemeral(true)).await;
@@ -61,16 +61,40 @@ impl<U, E> Context<'_, U, E> {
         }
     }
 
+    /// The locale of the invoking user. Only available in slash commands, because Discord
+    /// doesn't send it with messages.
+    pub fn locale(&self) -> Option<&str> {
+        match self {
+            Self::Slash(ctx) => Some(ctx.locale()),
+            Self::Prefix(_) => None,
+        }
+    }
+
     // Doesn't fit in with the rest of the functions here but it's convenient
     /// Warnings: clones the entire Guild instance out of the cache
     pub fn guild(&self) -> Option<serenity::Guild> {
         self.guild_id()?.to_guild_cached(self.discord())
     }
 
+    /// A unique ID of this command invocation: the invoking message ID in prefix commands, the
+    /// interaction ID in slash commands. Doesn't need the cache.
+    ///
+    /// The ID stays the same for the whole invocation, so it can be used to correlate logs or
+    /// pre- and post-command hooks. Note that when a prefix command is re-run because its message
+    /// was edited, the re-invocation has the same ID as the original invocation.
+    pub fn id(&self) -> u64 {
+        match self {
+            Self::Slash(ctx) => ctx.interaction.id.0,
+            Self::Prefix(ctx) => ctx.msg.id.0,
+        }
+    }
+
+    /// When this command invocation was created, derived from the snowflake returned by
+    /// [`Self::id`]. Like the ID, it's unchanged for edit-triggered re-invocations.
     pub fn created_at(&self) -> chrono::DateTime<chrono::Utc> {
         match self {
             Self::Slash(ctx) => ctx.interaction.id.created_at(),
-            Self::Prefix(ctx) => ctx.msg.timestamp,
+            Self::Prefix(ctx) => ctx.msg.id.created_at(),
         }
     }
 
@@ -81,6 +105,120 @@ impl<U, E> Context<'_, U, E> {
             Self::Prefix(ctx) => &ctx.msg.author,
         }
     }
+
+    /// Returns the guild member of the invoking user. Returns None outside of guilds or if the
+    /// member couldn't be retrieved.
+    ///
+    /// In slash commands, this is the member sent along with the interaction. In prefix commands,
+    /// the member is looked up in the cache or, failing that, fetched via HTTP.
+    pub async fn author_member(&self) -> Option<std::borrow::Cow<'_, serenity::Member>> {
+        match self {
+            Self::Slash(ctx) => ctx.author_member().await,
+            Self::Prefix(ctx) => ctx.author_member().await,
+        }
+    }
+
+    /// Returns the gateway heartbeat latency of the shard that received the invocation, or None
+    /// if it isn't known yet (e.g. no heartbeat has been acknowledged so far).
+    pub async fn ping(&self) -> Option<std::time::Duration> {
+        let shard_manager = self.framework().shard_manager()?;
+        let shard_manager = shard_manager.lock().await;
+        let runners = shard_manager.runners.lock().await;
+        runners
+            .get(&serenity::ShardId(self.discord().shard_id))?
+            .latency
+    }
+
+    /// Fetches the guild of the invocation via HTTP. Useful when [`Self::guild`] returns None
+    /// because the guild isn't cached. Returns None in DMs or if the request failed.
+    pub async fn partial_guild(&self) -> Option<serenity::PartialGuild> {
+        self.guild_id()?.to_partial_guild(self.discord()).await.ok()
+    }
+
+    /// Returns the guild channel of the invocation, or None in DMs. Threads are returned as
+    /// channels too.
+    ///
+    /// The channel is looked up in the cache, falling back to HTTP for uncached channels and
+    /// threads.
+    pub async fn guild_channel(&self) -> Option<serenity::GuildChannel> {
+        if self.guild_id().is_none() {
+            return None;
+        }
+        if let Some(guild) = self.guild() {
+            return crate::framework::guild_channel(*self, &guild, self.channel_id()).await;
+        }
+
+        match self.discord().http.get_channel(self.channel_id().0).await {
+            Ok(serenity::Channel::Guild(channel)) => Some(channel),
+            _ => None,
+        }
+    }
+
+    /// Reacts to the invocation message in prefix commands. Slash command invocations aren't
+    /// messages, so the response is reacted to instead, which must have been sent already.
+    pub async fn react(
+        &self,
+        reaction: impl Into<serenity::ReactionType>,
+    ) -> Result<serenity::Reaction, serenity::Error> {
+        match self {
+            Self::Slash(ctx) => {
+                let response = ctx
+                    .interaction
+                    .get_interaction_response(ctx.discord)
+                    .await?;
+                response.react(ctx.discord, reaction).await
+            }
+            Self::Prefix(ctx) => ctx.msg.react(ctx.discord, reaction).await,
+        }
+    }
+}
+
+impl<'a, U, E> Context<'a, U, E> {
+    /// Signals that the command may take a while to respond. In slash commands, this
+    /// [defers](crate::SlashContext::defer) the response, so that the next reply fills in the
+    /// deferred response. In prefix commands, this broadcasts typing until the next reply or until
+    /// the command finishes.
+    ///
+    /// Calling this multiple times is harmless. In slash commands, it does nothing if the command
+    /// has already responded or deferred.
+    pub async fn defer(self) -> Result<(), serenity::Error> {
+        match self {
+            Self::Slash(ctx) => ctx.defer().await,
+            Self::Prefix(ctx) => {
+                ctx.framework.start_typing(&ctx.discord.http, ctx.msg);
+                Ok(())
+            }
+        }
+    }
+
+    /// Like [`Self::defer`], but in slash commands, the eventual response will be ephemeral. See
+    /// [`crate::SlashContext::defer_ephemeral`].
+    pub async fn defer_ephemeral(self) -> Result<(), serenity::Error> {
+        match self {
+            Self::Slash(ctx) => ctx.defer_ephemeral().await,
+            Self::Prefix(ctx) => {
+                ctx.framework.start_typing(&ctx.discord.http, ctx.msg);
+                Ok(())
+            }
+        }
+    }
+
+    /// Shorthand of [`crate::say_reply`]
+    pub async fn say(
+        self,
+        text: impl Into<String>,
+    ) -> Result<crate::ReplyHandle<'a, U, E>, serenity::Error> {
+        crate::say_reply(self, text).await
+    }
+
+    /// Replies with the given text ephemerally, i.e. only visible to the invoking user. See
+    /// [`crate::CreateReply::ephemeral`] for the behavior in prefix commands.
+    pub async fn say_ephemeral(
+        self,
+        text: impl Into<String>,
+    ) -> Result<crate::ReplyHandle<'a, U, E>, serenity::Error> {
+        crate::send_reply(self, |f| f.content(text.into()).ephemeral(true)).await
+    }
 }
 
 pub enum CommandRef<'a, U, E> {
@@ -144,11 +282,107 @@ impl<'a, U, E> CommandErrorContext<'a, U, E> {
     }
 }
 
+/// An error that occurred while the framework handled an event, passed to
+/// [`FrameworkOptions::on_error`] along with an [`ErrorContext`].
+///
+/// Errors that originate in the framework itself are kept apart from the user error type `E`, so
+/// that `E` doesn't need to be convertible from the framework's error types.
+#[derive(Debug)]
+pub enum FrameworkError<E> {
+    /// Returned by user code, like a command, a check or the event listener
+    User(E),
+    /// The arguments of a prefix command invocation couldn't be parsed
+    ArgumentParse(crate::ArgumentParseError),
+    /// The arguments of a slash command invocation couldn't be parsed. The user has already been
+    /// sent an ephemeral error message.
+    SlashArgumentParse(crate::SlashArgumentParseError),
+    /// A Discord API request made by the framework itself failed, for example acknowledging an
+    /// interaction
+    Serenity(serenity::Error),
+    /// A command check returned false, so the command wasn't run
+    CommandCheckFailed,
+    /// The command is still on cooldown for this invocation, so it wasn't run
+    CooldownHit {
+        /// How long until the command can be invoked again
+        remaining_cooldown: std::time::Duration,
+    },
+}
+
+impl<E> FrameworkError<E> {
+    /// Returns the user error, if this error was returned by user code
+    pub fn user_error(&self) -> Option<&E> {
+        match self {
+            Self::User(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for FrameworkError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::User(e) => e.fmt(f),
+            Self::ArgumentParse(e) => e.fmt(f),
+            Self::SlashArgumentParse(e) => e.fmt(f),
+            Self::Serenity(e) => e.fmt(f),
+            Self::CommandCheckFailed => f.write_str("You can't use this command here"),
+            Self::CooldownHit { remaining_cooldown } => write!(
+                f,
+                "You're too fast. Please wait {} seconds before retrying",
+                remaining_cooldown.as_secs() + 1
+            ),
+        }
+    }
+}
+
+// Only requires Debug and Display of E, so that common error types which don't implement Error
+// themselves, like `Box<dyn Error>` and `anyhow::Error`, can be converted from FrameworkError
+// with `?`
+impl<E: std::fmt::Debug + std::fmt::Display> std::error::Error for FrameworkError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::User(_) => None,
+            Self::ArgumentParse(e) => Some(e),
+            Self::SlashArgumentParse(e) => Some(e),
+            Self::Serenity(e) => Some(e),
+            Self::CommandCheckFailed | Self::CooldownHit { .. } => None,
+        }
+    }
+}
+
+impl<E> From<crate::ArgumentParseError> for FrameworkError<E> {
+    fn from(e: crate::ArgumentParseError) -> Self {
+        Self::ArgumentParse(e)
+    }
+}
+
+impl<E> From<crate::SlashArgumentParseError> for FrameworkError<E> {
+    fn from(e: crate::SlashArgumentParseError) -> Self {
+        Self::SlashArgumentParse(e)
+    }
+}
+
+impl<E> From<serenity::Error> for FrameworkError<E> {
+    fn from(e: serenity::Error) -> Self {
+        Self::Serenity(e)
+    }
+}
+
 /// Contains the location of the error with location-specific context
 pub enum ErrorContext<'a, U, E> {
     Setup,
     Listener(&'a crate::Event<'a>),
     Command(CommandErrorContext<'a, U, E>),
+    /// Error in the [`crate::PrefixFrameworkOptions::unknown_command`] callback
+    UnknownCommand(crate::PrefixContext<'a, U, E>),
+    /// Error in the [`crate::PrefixFrameworkOptions::dynamic_prefix`] or
+    /// [`crate::PrefixFrameworkOptions::stripped_dynamic_prefix`] callback, with the message that
+    /// was being processed
+    DynamicPrefix(&'a serenity::Message),
+    /// Error in the autocomplete callback of a slash command parameter
+    Autocomplete(crate::AutocompleteErrorContext<'a, U, E>),
+    /// Error in a [`crate::ComponentHandler`]
+    Component(crate::ComponentContext<'a, U, E>),
 }
 
 impl<U, E> Clone for ErrorContext<'_, U, E> {
@@ -157,58 +391,149 @@ impl<U, E> Clone for ErrorContext<'_, U, E> {
             Self::Setup => Self::Setup,
             Self::Listener(x) => Self::Listener(x),
             Self::Command(x) => Self::Command(x.clone()),
+            Self::UnknownCommand(x) => Self::UnknownCommand(*x),
+            Self::DynamicPrefix(x) => Self::DynamicPrefix(x),
+            Self::Autocomplete(x) => Self::Autocomplete(x.clone()),
+            Self::Component(x) => Self::Component(*x),
+        }
+    }
+}
+
+/// A command with a prefix implementation, a slash implementation, or both, plus metadata shared
+/// between the two like category and subcommands. The `#[poise::command]` macro generates both
+/// implementations from the same function and attributes, so they agree on checks, permissions
+/// and so on.
+pub struct Command<U, E> {
+    /// Main name of the command
+    pub name: &'static str,
+    /// Identifier for the category that this command will be displayed in for help commands.
+    pub category: Option<&'static str>,
+    /// Possible subcommands. Slash implementations of subcommands are registered as slash
+    /// subcommands, or subcommand groups if they have subcommands themselves, of this command's
+    /// slash implementation.
+    pub subcommands: Vec<Command<U, E>>,
+    /// The prefix implementation, if this command can be invoked via prefix
+    pub prefix_action: Option<crate::PrefixCommand<U, E>>,
+    /// The slash or context menu implementation, if this command can be invoked via interactions
+    pub slash_action: Option<crate::SlashCommand<U, E>>,
+}
+
+impl<U, E> From<crate::PrefixCommand<U, E>> for Command<U, E> {
+    fn from(prefix_command: crate::PrefixCommand<U, E>) -> Self {
+        Self {
+            name: prefix_command.name,
+            category: None,
+            subcommands: Vec::new(),
+            prefix_action: Some(prefix_command),
+            slash_action: None,
+        }
+    }
+}
+
+impl<U, E> From<crate::SlashCommand<U, E>> for Command<U, E> {
+    fn from(slash_command: crate::SlashCommand<U, E>) -> Self {
+        Self {
+            name: slash_command.name,
+            category: None,
+            subcommands: Vec::new(),
+            prefix_action: None,
+            slash_action: Some(slash_command),
+        }
+    }
+}
+
+impl<U, E> Command<U, E> {
+    /// The guilds that the slash implementation of this command is restricted to, see
+    /// [`crate::SlashCommandOptions::guilds`]
+    pub fn slash_guilds(&self) -> Option<&[serenity::GuildId]> {
+        self.slash_action
+            .as_ref()
+            .and_then(|slash_command| slash_command.options.guilds.as_deref())
+    }
+
+    /// Whether the slash implementation of this command is registered in the given guild, or
+    /// outside of guilds if None. Always true for commands that aren't restricted to certain
+    /// guilds.
+    pub fn is_available_in(&self, guild_id: Option<serenity::GuildId>) -> bool {
+        match self.slash_guilds() {
+            Some(guilds) => guild_id.map_or(false, |guild_id| guilds.contains(&guild_id)),
+            None => true,
+        }
+    }
+}
+
+/// For command definitions in the form of separate prefix and slash implementations
+impl<U, E>
+    From<(
+        crate::PrefixCommand<U, E>,
+        Option<crate::SlashCommand<U, E>>,
+    )> for Command<U, E>
+{
+    fn from(
+        (prefix_command, slash_command): (
+            crate::PrefixCommand<U, E>,
+            Option<crate::SlashCommand<U, E>>,
+        ),
+    ) -> Self {
+        Self {
+            slash_action: slash_command,
+            ..prefix_command.into()
         }
     }
 }
 
 pub struct CommandBuilder<U, E> {
-    prefix_command: crate::PrefixCommandMeta<U, E>,
-    slash_command: Option<crate::SlashCommand<U, E>>,
+    command: Command<U, E>,
 }
 
 impl<U, E> CommandBuilder<U, E> {
     pub fn category(&mut self, category: &'static str) -> &mut Self {
-        self.prefix_command.category = Some(category);
+        self.command.category = Some(category);
         self
     }
 
     pub fn subcommand(
         &mut self,
-        definition: (
-            crate::PrefixCommand<U, E>,
-            Option<crate::SlashCommand<U, E>>,
-        ),
+        definition: impl Into<Command<U, E>>,
         meta_builder: impl FnOnce(&mut Self) -> &mut Self,
     ) -> &mut Self {
-        // STUB: do slash support
-
-        let (prefix_command, slash_command) = definition;
-
-        let prefix_command = crate::PrefixCommandMeta {
-            command: prefix_command,
-            category: None,
-            subcommands: Vec::new(),
-        };
-
         let mut builder = CommandBuilder {
-            prefix_command,
-            slash_command,
+            command: definition.into(),
         };
         meta_builder(&mut builder);
 
-        self.prefix_command.subcommands.push(builder.prefix_command);
+        self.command.subcommands.push(builder.command);
+        self
+    }
 
+    /// Restricts the slash implementation of this command to the given guilds. See
+    /// [`crate::SlashCommandOptions::guilds`].
+    pub fn guilds(&mut self, guilds: Vec<serenity::GuildId>) -> &mut Self {
+        if let Some(slash_command) = &mut self.command.slash_action {
+            slash_command.options.guilds = Some(guilds);
+        }
         self
     }
 }
 
 pub struct FrameworkOptions<U, E> {
-    /// Provide a callback to be invoked when any user code yields an error.
-    pub on_error: fn(E, ErrorContext<'_, U, E>) -> BoxFuture<'_, ()>,
+    /// List of bot commands, both prefix and slash.
+    pub commands: Vec<Command<U, E>>,
+    /// Handlers for message component interactions, like button clicks. The first handler whose
+    /// custom ID pattern matches is run. Component interactions are passed to [`Self::listener`]
+    /// as well, regardless of whether a handler matched.
+    pub component_handlers: Vec<crate::ComponentHandler<U, E>>,
+    /// Provide a callback to be invoked when any user code or the framework itself yields an
+    /// error.
+    pub on_error: fn(FrameworkError<E>, ErrorContext<'_, U, E>) -> BoxFuture<'_, ()>,
     /// Called before every command
     pub pre_command: fn(Context<'_, U, E>) -> BoxFuture<'_, ()>,
     /// Default set of allowed mentions to use for all responses
     pub allowed_mentions: Option<serenity::CreateAllowedMentions>,
+    /// Invoked on every reply after the command's builder closure ran, right before the reply is
+    /// sent or an existing response is edited with it. Can be used to post-process all responses,
+    /// for example to add an embed footer everywhere.
+    pub reply_callback: Option<fn(Context<'_, U, E>, &mut crate::CreateReply<'_>)>,
     /// Called on every Discord event. Can be used to react to non-command events, like messages
     /// deletions or guild updates.
     pub listener: for<'a> fn(
@@ -217,6 +542,22 @@ pub struct FrameworkOptions<U, E> {
         &'a crate::Framework<U, E>,
         &'a U,
     ) -> BoxFuture<'a, Result<(), E>>,
+    /// Called on every message update with the updated message, i.e. the previous message with the
+    /// update applied. Runs regardless of whether edit tracking is enabled.
+    ///
+    /// If the previous message isn't cached, only the fields included in the update are filled in;
+    /// e.g. when Discord resolves a link embed, the message content and author may be empty.
+    ///
+    /// Useful to implement custom edit handling, like re-running moderation filters. Errors are
+    /// passed to [`Self::on_error`] with [`ErrorContext::Listener`].
+    pub message_update_listener: Option<
+        for<'a> fn(
+            &'a serenity::Context,
+            &'a serenity::Message,
+            &'a crate::Framework<U, E>,
+            &'a U,
+        ) -> BoxFuture<'a, Result<(), E>>,
+    >,
     /// Slash command specific options.
     pub slash_options: crate::SlashFrameworkOptions<U, E>,
     /// Prefix command specific options.
@@ -225,7 +566,86 @@ pub struct FrameworkOptions<U, E> {
     pub owners: std::collections::HashSet<serenity::UserId>,
 }
 
+/// Returned from [`FrameworkOptions::validate`] when two commands on the same level share a name
+/// or alias
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateCommandName {
+    /// Main name of the first command
+    pub first_command: &'static str,
+    /// Main name of the second command
+    pub second_command: &'static str,
+    /// The name or alias both commands use
+    pub name: &'static str,
+}
+
+impl std::fmt::Display for DuplicateCommandName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "commands `{}` and `{}` both use the name or alias `{}`",
+            self.first_command, self.second_command, self.name
+        )
+    }
+}
+
+impl std::error::Error for DuplicateCommandName {}
+
+/// Finds the first name or alias collision among the prefix implementations of the given
+/// commands, and recursively among the subcommands of each command
+fn find_duplicate_command_name<U, E>(
+    commands: &[Command<U, E>],
+    case_insensitive: bool,
+) -> Option<DuplicateCommandName> {
+    // (name or alias, main name of the command it belongs to)
+    let mut seen_names: Vec<(&'static str, &'static str)> = Vec::new();
+    for command_meta in commands {
+        let command = match &command_meta.prefix_action {
+            Some(x) => x,
+            None => continue,
+        };
+        for &name in std::iter::once(&command.name).chain(command.options.aliases) {
+            let collision = seen_names.iter().find(|&&(seen_name, _)| {
+                if case_insensitive {
+                    seen_name.eq_ignore_ascii_case(name)
+                } else {
+                    seen_name == name
+                }
+            });
+            if let Some(&(_, other_command)) = collision {
+                return Some(DuplicateCommandName {
+                    first_command: other_command,
+                    second_command: command.name,
+                    name,
+                });
+            }
+            seen_names.push((name, command.name));
+        }
+
+        if let Some(duplicate) =
+            find_duplicate_command_name(&command_meta.subcommands, case_insensitive)
+        {
+            return Some(duplicate);
+        }
+    }
+    None
+}
+
 impl<U, E> FrameworkOptions<U, E> {
+    /// Checks that no two prefix commands on the same level (top-level or subcommands of the
+    /// same parent) share a name or alias, respecting
+    /// [`crate::PrefixFrameworkOptions::case_insensitive_commands`].
+    ///
+    /// Called by [`crate::Framework::start`], which panics on failure.
+    pub fn validate(&self) -> Result<(), DuplicateCommandName> {
+        match find_duplicate_command_name(
+            &self.commands,
+            self.prefix_options.case_insensitive_commands,
+        ) {
+            Some(duplicate) => Err(duplicate),
+            None => Ok(()),
+        }
+    }
+
     /// Add a command definition, which can include a prefix implementation and a slash
     /// implementation, to the framework.
     ///
@@ -246,39 +666,39 @@ impl<U, E> FrameworkOptions<U, E> {
     /// ```
     pub fn command(
         &mut self,
-        definition: (
-            crate::PrefixCommand<U, E>,
-            Option<crate::SlashCommand<U, E>>,
-        ),
+        definition: impl Into<Command<U, E>>,
         meta_builder: impl FnOnce(&mut CommandBuilder<U, E>) -> &mut CommandBuilder<U, E>,
     ) {
-        let (prefix_command, slash_command) = definition;
-
-        let prefix_command = crate::PrefixCommandMeta {
-            command: prefix_command,
-            category: None,
-            subcommands: Vec::new(),
-        };
-
         let mut builder = CommandBuilder {
-            prefix_command,
-            slash_command,
+            command: definition.into(),
         };
         meta_builder(&mut builder);
 
-        self.prefix_options.commands.push(builder.prefix_command);
-
-        if let Some(slash_command) = builder.slash_command {
-            self.slash_options.commands.push(slash_command);
-        }
+        self.commands.push(builder.command);
     }
 }
 
 impl<U: Send + Sync, E: std::fmt::Display + Send> Default for FrameworkOptions<U, E> {
     fn default() -> Self {
         Self {
+            commands: Vec::new(),
+            component_handlers: Vec::new(),
             on_error: |error, ctx| {
                 Box::pin(async move {
+                    let command_ctx = match &ctx {
+                        ErrorContext::Command(ctx) => Some(ctx.ctx()),
+                        _ => None,
+                    };
+                    let error = match command_ctx {
+                        Some(command_ctx) => {
+                            match crate::defaults::on_rejected_invocation(error, command_ctx).await
+                            {
+                                Ok(()) => return,
+                                Err(error) => error,
+                            }
+                        }
+                        None => error,
+                    };
                     match ctx {
                         ErrorContext::Setup => println!("Error in user data setup: {}", error),
                         ErrorContext::Listener(event) => println!(
@@ -293,12 +713,43 @@ impl<U: Send + Sync, E: std::fmt::Display + Send> Default for FrameworkOptions<U
                             );
                         }
                         ErrorContext::Command(CommandErrorContext::Slash(ctx)) => {
-                            println!("Error in slash command \"{}\": {}", ctx.command.name, error);
+                            println!(
+                                "Error in slash command \"{}\": {}",
+                                ctx.ctx.qualified_command_name(),
+                                error
+                            );
+                        }
+                        ErrorContext::DynamicPrefix(msg) => {
+                            println!(
+                                "Dynamic prefix failed for message \"{}\": {}",
+                                msg.content, error
+                            );
+                        }
+                        ErrorContext::UnknownCommand(ctx) => {
+                            println!(
+                                "Error in unknown command callback from message \"{}\": {}",
+                                &ctx.msg.content, error
+                            );
+                        }
+                        ErrorContext::Autocomplete(ctx) => {
+                            println!(
+                                "Error in autocomplete of /{} parameter \"{}\" for input \"{}\": {}",
+                                ctx.ctx.command.name, ctx.parameter_name, ctx.partial_input, error
+                            );
+                        }
+                        ErrorContext::Component(ctx) => {
+                            println!(
+                                "Error in component handler for custom ID \"{}\": {}",
+                                ctx.custom_id(),
+                                error
+                            );
                         }
                     }
                 })
             },
             listener: |_, _, _, _| Box::pin(async { Ok(()) }),
+            message_update_listener: None,
+            reply_callback: None,
             pre_command: |_| Box::pin(async {}),
             allowed_mentions: Some({
                 let mut f = serenity::CreateAllowedMentions::default();
@@ -317,3 +768,85 @@ pub enum Arguments<'a> {
     Prefix(&'a str),
     Slash(&'a [serenity::ApplicationCommandInteractionDataOption]),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::framework::prefix::test::dummy_command;
+
+    #[test]
+    fn test_framework_error_into_boxed_error() {
+        type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+        let error = FrameworkError::<BoxError>::User("no such tag".into());
+        assert_eq!(
+            error.user_error().map(|e| e.to_string()),
+            Some("no such tag".to_owned())
+        );
+        let boxed: BoxError = error.into();
+        assert_eq!(boxed.to_string(), "no such tag");
+
+        let error =
+            FrameworkError::<BoxError>::from(crate::ArgumentParseError("expected a number".into()));
+        assert!(error.user_error().is_none());
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn test_framework_error_rejected_invocation() {
+        let error = FrameworkError::<String>::CooldownHit {
+            remaining_cooldown: std::time::Duration::from_millis(2500),
+        };
+        assert_eq!(
+            error.to_string(),
+            "You're too fast. Please wait 3 seconds before retrying"
+        );
+        assert!(error.user_error().is_none());
+        assert!(std::error::Error::source(&error).is_none());
+
+        let error = FrameworkError::<String>::CommandCheckFailed;
+        assert!(error.user_error().is_none());
+        assert!(std::error::Error::source(&error).is_none());
+    }
+
+    #[test]
+    fn test_find_duplicate_command_name() {
+        let commands = vec![
+            dummy_command("play", &["p"], vec![]),
+            dummy_command("pause", &["P"], vec![]),
+        ];
+        assert_eq!(find_duplicate_command_name(&commands, false), None);
+        assert_eq!(
+            find_duplicate_command_name(&commands, true),
+            Some(DuplicateCommandName {
+                first_command: "play",
+                second_command: "pause",
+                name: "P",
+            })
+        );
+
+        // Same names on different levels are fine
+        let commands = vec![
+            dummy_command("add", &[], vec![]),
+            dummy_command("tag", &[], vec![dummy_command("add", &[], vec![])]),
+        ];
+        assert_eq!(find_duplicate_command_name(&commands, true), None);
+
+        let commands = vec![dummy_command(
+            "tag",
+            &[],
+            vec![
+                dummy_command("add", &["a"], vec![]),
+                dummy_command("all", &["a"], vec![]),
+            ],
+        )];
+        assert_eq!(
+            find_duplicate_command_name(&commands, true),
+            Some(DuplicateCommandName {
+                first_command: "add",
+                second_command: "all",
+                name: "a",
+            })
+        );
+    }
+}